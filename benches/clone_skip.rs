@@ -0,0 +1,64 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Compare the cost of `as_tracked_mut()` with and without listeners.
+//!
+//! Without listeners, no copy of the data is made, so modifying a large
+//! value does not allocate. Run with `cargo bench --bench clone_skip`.
+
+extern crate data_tracker;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use data_tracker::DataTracker;
+
+/// Counts allocations made through the global allocator.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERATIONS: usize = 10_000;
+
+fn run(name: &str, tracked_data: &mut DataTracker<Vec<u8>, u8>) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let mut x = tracked_data.as_tracked_mut();
+        x[0] = i as u8;
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!("{}: {} allocations, {:?} per modification",
+             name,
+             allocations,
+             elapsed / ITERATIONS as u32);
+}
+
+fn main() {
+    let mut tracked_data = DataTracker::new(vec![0; 64 * 1024]);
+    run("no listeners", &mut tracked_data);
+
+    tracked_data.add_listener(0, |_: &Vec<u8>, _: &Vec<u8>| {});
+    run("one listener", &mut tracked_data);
+}
@@ -0,0 +1,217 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Derive macro for field-level change tracking with `data_tracker`.
+//!
+//! `#[derive(TrackFields)]` on a struct `Foo` with named fields generates an
+//! enum `FooField` with one variant per field (in CamelCase) and implements
+//! `data_tracker::TrackFields` for `Foo`, so that
+//! `data_tracker::FieldTracker` can notify listeners of individual fields.
+//! Every tracked field must implement `PartialEq`. Fields marked with
+//! `#[track(skip)]` get no variant and their changes are ignored.
+//!
+//! ```ignore
+//! #[derive(Clone, PartialEq, TrackFields)]
+//! struct Settings {
+//!     volume: u8,
+//!     muted: bool,
+//!     #[track(skip)]
+//!     last_accessed: u64,
+//! }
+//!
+//! // Generated:
+//! // enum SettingsField { Volume, Muted }
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Derive `data_tracker::TrackFields` for a struct with named fields.
+#[proc_macro_derive(TrackFields, attributes(track))]
+pub fn derive_track_fields(input: TokenStream) -> TokenStream {
+    let output = match parse_struct(input) {
+        Ok(parsed) => generate(&parsed),
+        Err(msg) => format!("compile_error!({:?});", msg),
+    };
+    output.parse().unwrap()
+}
+
+struct Parsed {
+    vis: String,
+    name: String,
+    fields: Vec<String>,
+}
+
+/// Return `Ok(true)` if the attribute body is `track(skip)`, `Ok(false)` if it
+/// is an unrelated attribute.
+fn is_skip_attr(attr: &TokenStream) -> Result<bool, String> {
+    let tokens: Vec<TokenTree> = attr.clone().into_iter().collect();
+    match tokens.first() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "track" => {}
+        _ => return Ok(false),
+    }
+    match tokens.get(1) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis &&
+                                         group.stream().to_string() == "skip" &&
+                                         tokens.len() == 2 => Ok(true),
+        _ => Err("expected `#[track(skip)]`".to_string()),
+    }
+}
+
+/// Consume a visibility qualifier such as `pub` or `pub(crate)` starting at
+/// `tokens[*i]`, returning it as a string.
+fn parse_vis(tokens: &[TokenTree], i: &mut usize) -> String {
+    match tokens.get(*i) {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+            *i += 1;
+            if let Some(TokenTree::Group(group)) = tokens.get(*i) {
+                if group.delimiter() == Delimiter::Parenthesis {
+                    *i += 1;
+                    return format!("pub({})", group.stream());
+                }
+            }
+            "pub".to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+fn parse_struct(input: TokenStream) -> Result<Parsed, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut i = 0;
+
+    // Outer attributes.
+    while let Some(TokenTree::Punct(punct)) = tokens.get(i) {
+        if punct.as_char() != '#' {
+            break;
+        }
+        i += 2;
+    }
+
+    let vis = parse_vis(&tokens, &mut i);
+
+    match tokens.get(i) {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => i += 1,
+        _ => return Err("TrackFields can only be derived for structs".to_string()),
+    }
+    let name = match tokens.get(i) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected struct name".to_string()),
+    };
+    i += 1;
+
+    let body = match tokens.get(i) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err("TrackFields does not support generic structs".to_string())
+        }
+        _ => return Err("TrackFields requires a struct with named fields".to_string()),
+    };
+
+    Ok(Parsed {
+        vis,
+        name,
+        fields: parse_fields(body)?,
+    })
+}
+
+/// Return the names of all fields not marked `#[track(skip)]`.
+fn parse_fields(body: TokenStream) -> Result<Vec<String>, String> {
+    let tokens: Vec<TokenTree> = body.into_iter().collect();
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let mut skip = false;
+        while let Some(TokenTree::Punct(punct)) = tokens.get(i) {
+            if punct.as_char() != '#' {
+                break;
+            }
+            if let Some(TokenTree::Group(group)) = tokens.get(i + 1) {
+                skip |= is_skip_attr(&group.stream())?;
+            }
+            i += 2;
+        }
+
+        parse_vis(&tokens, &mut i);
+
+        let name = match tokens.get(i) {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            _ => return Err("expected field name".to_string()),
+        };
+        i += 1;
+
+        // Skip `: Type` up to the next comma outside of angle brackets.
+        let mut depth = 0;
+        let mut after_dash = false;
+        while let Some(token) = tokens.get(i) {
+            i += 1;
+            if let TokenTree::Punct(ref punct) = *token {
+                match punct.as_char() {
+                    '<' => depth += 1,
+                    '>' if !after_dash => depth -= 1,
+                    ',' if depth == 0 => break,
+                    _ => {}
+                }
+                after_dash = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+            } else {
+                after_dash = false;
+            }
+        }
+
+        if !skip {
+            fields.push(name);
+        }
+    }
+    Ok(fields)
+}
+
+/// Convert a `snake_case` field name to a `CamelCase` variant name.
+fn variant_name(field: &str) -> String {
+    let field = field.trim_start_matches("r#");
+    let mut out = String::new();
+    for part in field.split('_').filter(|part| !part.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            out.push_str(chars.as_str());
+        }
+    }
+    out
+}
+
+fn generate(parsed: &Parsed) -> String {
+    let enum_name = format!("{}Field", parsed.name);
+    let variants: Vec<String> = parsed.fields.iter().map(|f| variant_name(f)).collect();
+    let mut checks = String::new();
+    for (field, variant) in parsed.fields.iter().zip(variants.iter()) {
+        checks.push_str(&format!("if old.{f} != new.{f} {{ changed.push({e}::{v}); }}\n",
+                                 f = field,
+                                 e = enum_name,
+                                 v = variant));
+    }
+    format!("/// Identifies a tracked field of `{name}`.
+             #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+             {vis} enum {enum_name} {{ {variants} }}
+
+             impl ::data_tracker::TrackFields for {name} {{
+                 type Field = {enum_name};
+
+                 #[allow(unused_mut)]
+                 fn changed_fields(old: &Self, new: &Self) -> Vec<{enum_name}> {{
+                     let mut changed = Vec::new();
+                     {checks}
+                     changed
+                 }}
+             }}",
+            name = parsed.name,
+            vis = parsed.vis,
+            enum_name = enum_name,
+            variants = variants.join(", "),
+            checks = checks)
+}
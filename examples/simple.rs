@@ -11,9 +11,9 @@ fn main() {
     let mut data = DataTracker::new(MyData { a: 1 });
     let key = 0; // Keep the key to remove the callback later.
     data.add_listener(key,
-                      Box::new(|old_value: &MyData, new_value: &MyData| {
+                      |old_value: &MyData, new_value: &MyData| {
                           println!("changed {:?} -> {:?}", old_value, new_value);
-                      }));
+                      });
 
     {
         // Create x, a (non-mutable) reference to original data.
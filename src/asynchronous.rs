@@ -0,0 +1,236 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Listeners which return a future.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::{DataTracker, ListenerKey, Modifier};
+
+/// The future returned by an `AsyncOnChanged` callback.
+#[cfg(not(feature = "no_send"))]
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+#[cfg(feature = "no_send")]
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Trait defining an asynchronous change notification callback function.
+///
+/// The returned future must not borrow the values, so clone whatever it
+/// needs from them.
+#[cfg(not(feature = "no_send"))]
+pub trait AsyncOnChanged<T>: Send {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> BoxFuture;
+}
+
+#[cfg(feature = "no_send")]
+pub trait AsyncOnChanged<T> {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> BoxFuture;
+}
+
+/// Lets a boxed callback be passed wherever a callback is expected.
+impl<T> AsyncOnChanged<T> for Box<dyn AsyncOnChanged<T>> {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> BoxFuture {
+        (**self).on_changed(old_value, new_value)
+    }
+}
+
+#[cfg(not(feature = "no_send"))]
+impl<F, T, Fut> AsyncOnChanged<T> for F
+    where F: Fn(&T, &T) -> Fut + Send,
+          Fut: Future<Output = ()> + Send + 'static
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) -> BoxFuture {
+        Box::pin(self(old_value, new_value))
+    }
+}
+
+#[cfg(feature = "no_send")]
+impl<F, T, Fut> AsyncOnChanged<T> for F
+    where F: Fn(&T, &T) -> Fut,
+          Fut: Future<Output = ()> + 'static
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) -> BoxFuture {
+        Box::pin(self(old_value, new_value))
+    }
+}
+
+/// Future returned by
+/// [`Modifier::commit_async()`](./struct.Modifier.html#method.commit_async).
+///
+/// It completes with `true` if the data changed, once the futures of all
+/// asynchronous callbacks have completed. These futures are polled
+/// concurrently.
+#[must_use = "asynchronous callbacks only run when the future is awaited"]
+pub struct Commit {
+    pending: Vec<BoxFuture>,
+    changed: bool,
+}
+
+impl Future for Commit {
+    type Output = bool;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<bool> {
+        self.pending.retain_mut(|future| future.as_mut().poll(cx).is_pending());
+        if self.pending.is_empty() {
+            Poll::Ready(self.changed)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Add an asynchronous callback.
+    ///
+    /// Since `Drop` cannot wait for a future, asynchronous callbacks are only
+    /// called by
+    /// [`Modifier::commit_async()`](./struct.Modifier.html#method.commit_async).
+    /// Changes made through other means do not reach them.
+    ///
+    /// ```edition2018
+    /// # use data_tracker::DataTracker;
+    /// # async fn run() {
+    /// let mut tracked_data = DataTracker::new(1);
+    /// tracked_data.add_async_listener(0, Box::new(|_: &i32, new_value: &i32| {
+    ///     let new_value = *new_value;
+    ///     async move {
+    ///         println!("storing {}", new_value);
+    ///     }
+    /// }));
+    /// let mut x = tracked_data.as_tracked_mut();
+    /// *x = 2;
+    /// assert!(x.commit_async().await);
+    /// # }
+    /// ```
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_async_listener<C>(&mut self,
+                                 key: K,
+                                 callback: C)
+                                 -> Option<Box<dyn AsyncOnChanged<T>>>
+        where C: AsyncOnChanged<T> + 'static
+    {
+        self.inner.async_listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove an asynchronous callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    pub fn remove_async_listener(&mut self, key: &K) -> Option<Box<dyn AsyncOnChanged<T>>> {
+        self.inner.async_listeners.remove(key)
+    }
+}
+
+impl<'a, T, K> Modifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    /// Like `commit()`, but also call the asynchronous callbacks.
+    ///
+    /// The regular callbacks are called right away. The returned future
+    /// completes once the futures of all asynchronous callbacks (see
+    /// [`DataTracker::add_async_listener()`](./struct.DataTracker.html#method.add_async_listener))
+    /// have completed.
+    pub fn commit_async(mut self) -> Commit {
+        let mut pending = Vec::new();
+        let changed = match self.settle() {
+            Some(ref orig_copy) => {
+                self.inner_ref.notify_listeners(orig_copy);
+                let new_value = &self.inner_ref.value;
+                self.inner_ref.async_listeners.dispatch(|callback| {
+                    pending.push(callback.on_changed(orig_copy, new_value));
+                    Ok(())
+                });
+                true
+            }
+            None => false,
+        };
+        Commit { pending, changed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{Future, poll_fn};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use super::super::DataTracker;
+
+    #[test]
+    fn commit_async() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+
+        let seen2 = seen.clone();
+        tracked_data.add_async_listener(0,
+                                        move |_: &i32, new_value: &i32| {
+            let seen = seen2.clone();
+            let new_value = *new_value;
+            // Complete on the second poll.
+            let mut polled = false;
+            poll_fn(move |_| if polled {
+                seen.lock().unwrap().push(new_value);
+                Poll::Ready(())
+            } else {
+                polled = true;
+                Poll::Pending
+            })
+        });
+
+        // Changes which are not committed asynchronously do not reach it.
+        tracked_data.set(2);
+
+        let mut x = tracked_data.as_tracked_mut();
+        *x = 3;
+        let mut commit = x.commit_async();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(std::pin::Pin::new(&mut commit).poll(&mut cx).is_pending());
+        assert!(seen.lock().unwrap().is_empty());
+        assert!(std::pin::Pin::new(&mut commit).poll(&mut cx) == Poll::Ready(true));
+        assert!(*seen.lock().unwrap() == vec![3]);
+
+        let x = tracked_data.as_tracked_mut();
+        let mut commit = x.commit_async();
+        assert!(std::pin::Pin::new(&mut commit).poll(&mut cx) == Poll::Ready(false));
+    }
+
+    #[test]
+    fn commit_async_version() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        // Without observers, no copy is made, so the version is bumped.
+        let x = tracked_data.as_tracked_mut();
+        drop(x.commit_async());
+        assert!(tracked_data.version() == 1);
+        let mut x = tracked_data.as_tracked_mut();
+        x.mark_clean();
+        drop(x.commit_async());
+        assert!(tracked_data.version() == 1);
+
+    }
+
+    #[test]
+    fn commit_async_counts() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        let mut x = tracked_data.as_tracked_mut();
+        *x = 2;
+        drop(x.commit_async());
+        drop(tracked_data.as_tracked_mut().commit_async());
+        assert!(tracked_data.change_count() == 1 && tracked_data.noop_count() == 1);
+    }
+}
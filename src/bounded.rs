@@ -0,0 +1,126 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Delivery of change events through a queue of limited capacity.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+use super::{MaybeSend, OnChanged};
+
+/// Which change is dropped when a bounded queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Drop the oldest queued change to make room for the new one.
+    Oldest,
+    /// Drop the new change, keeping the queued ones.
+    Newest,
+}
+
+struct State<T> {
+    queue: VecDeque<(T, T)>,
+    capacity: usize,
+    policy: DropPolicy,
+    dropped: u64,
+    closed: bool,
+}
+
+type Shared<T> = Arc<(Mutex<State<T>>, Condvar)>;
+
+/// The listener side of a `BoundedReceiver`.
+pub struct BoundedSender<T> {
+    shared: Shared<T>,
+}
+
+impl<T> OnChanged<T> for BoundedSender<T>
+    where T: Clone + MaybeSend
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let (ref lock, ref cvar) = *self.shared;
+        let mut state = lock.lock().unwrap();
+        if state.queue.len() >= state.capacity {
+            state.dropped += 1;
+            if state.capacity == 0 || state.policy == DropPolicy::Newest {
+                return;
+            }
+            state.queue.pop_front();
+        }
+        state.queue.push_back((old_value.clone(), new_value.clone()));
+        cvar.notify_one();
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        let (ref lock, ref cvar) = *self.shared;
+        lock.lock().unwrap().closed = true;
+        cvar.notify_all();
+    }
+}
+
+/// Receives `(old_value, new_value)` change events queued with a limited
+/// capacity.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::add_bounded_sender()`](./struct.DataTracker.html#method.add_bounded_sender).
+pub struct BoundedReceiver<T> {
+    shared: Shared<T>,
+}
+
+impl<T> BoundedReceiver<T> {
+    /// Return the oldest queued change, or `None` if the queue is empty.
+    pub fn try_recv(&self) -> Option<(T, T)> {
+        self.shared.0.lock().unwrap().queue.pop_front()
+    }
+
+    /// Wait for the oldest queued change.
+    ///
+    /// Returns `None` once the queue is empty and no more changes can
+    /// arrive, because the `DataTracker` was dropped or the listener removed.
+    pub fn recv(&self) -> Option<(T, T)> {
+        let (ref lock, ref cvar) = *self.shared;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if let Some(change) = state.queue.pop_front() {
+                return Some(change);
+            }
+            if state.closed {
+                return None;
+            }
+            state = cvar.wait(state).unwrap();
+        }
+    }
+
+    /// Return the number of changes currently queued.
+    pub fn len(&self) -> usize {
+        self.shared.0.lock().unwrap().queue.len()
+    }
+
+    /// Return `true` if no changes are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the number of changes dropped because the queue was full.
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.0.lock().unwrap().dropped
+    }
+}
+
+/// Create a connected listener and `BoundedReceiver` pair.
+pub fn channel<T>(capacity: usize, policy: DropPolicy) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    let shared = Arc::new((Mutex::new(State {
+                               queue: VecDeque::new(),
+                               capacity,
+                               policy,
+                               dropped: 0,
+                               closed: false,
+                           }),
+                           Condvar::new()));
+    (BoundedSender { shared: shared.clone() }, BoundedReceiver { shared })
+}
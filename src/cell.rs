@@ -0,0 +1,263 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A tracker with shared ownership within a single thread.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use std::cell::{RefCell, RefMut};
+use std::ops::DerefMut;
+
+use super::{resume_panic, Inner, ListenerKey, Listeners, OnChanged};
+
+/// The state behind the handles of a `TrackerCell` or a `SharedTracker`.
+pub(crate) struct State<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    pub(crate) inner: Inner<T, K>,
+    /// Detected changes which have not been passed to the listeners yet.
+    pending: VecDeque<(T, T)>,
+    /// `true` while some handle is calling the listeners. The listeners are
+    /// then moved out of `inner`, which holds only those added meanwhile.
+    dispatching: bool,
+    /// Keys removed while dispatching, applied when dispatching ends.
+    removed: Vec<K>,
+    /// The number of threads waiting for a change, which need changes to be
+    /// detected even without listeners.
+    pub(crate) waiters: usize,
+}
+
+impl<T, K> State<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    pub fn new(value: T) -> State<T, K> {
+        State {
+            inner: Inner::new(value),
+            pending: VecDeque::new(),
+            dispatching: false,
+            removed: Vec::new(),
+            waiters: 0,
+        }
+    }
+
+    pub fn remove_listener(&mut self, key: &K) -> Option<Box<dyn OnChanged<T>>>
+        where K: Clone
+    {
+        if self.dispatching {
+            self.removed.push(key.clone());
+        }
+        self.inner.listeners.remove(key)
+    }
+
+    /// Modify the data by calling `f` with `state` held and deliver the
+    /// change, if any.
+    ///
+    /// `lock` acquires the state again after it was released to call the
+    /// listeners.
+    pub fn modify<G, L, F, R>(mut state: G, lock: L, f: F) -> R
+        where G: DerefMut<Target = State<T, K>>,
+              L: Fn() -> G,
+              F: FnOnce(&mut T) -> R
+    {
+        let orig_copy = if state.dispatching || state.waiters > 0 || state.inner.is_observed() {
+            Some(state.inner.value.clone())
+        } else {
+            None
+        };
+        let result = f(&mut state.inner.value);
+        if let Some(orig_copy) = orig_copy {
+            if state.inner.changed_from(&orig_copy) {
+                state.inner.version += 1;
+                let new_value = state.inner.value.clone();
+                state.pending.push_back((orig_copy, new_value));
+                State::dispatch(state, lock);
+            }
+        } else {
+            // Without a copy a change cannot be ruled out, so count one.
+            state.inner.version += 1;
+        }
+        result
+    }
+
+    /// Deliver the pending changes unless another call is already doing so.
+    fn dispatch<G, L>(mut state: G, lock: L)
+        where G: DerefMut<Target = State<T, K>>,
+              L: Fn() -> G
+    {
+        if state.dispatching {
+            return;
+        }
+        state.dispatching = true;
+        let mut listeners = std::mem::replace(&mut state.inner.listeners, Listeners::new());
+        let mut panic = None;
+        while let Some((old_value, new_value)) = state.pending.pop_front() {
+            drop(state);
+            let (_, p) = listeners.notify_catching(&old_value, &new_value);
+            panic = panic.or(p);
+            state = lock();
+        }
+
+        // Apply the registrations made while dispatching.
+        for key in state.removed.drain(..) {
+            listeners.remove(&key);
+        }
+        let added = std::mem::replace(&mut state.inner.listeners, Listeners::new());
+        let mut added: Vec<_> = added.fn_map.into_iter().collect();
+        added.sort_by_key(|(_, l)| l.seq);
+        for (key, l) in added {
+            listeners.insert_listener(key, l);
+        }
+        state.inner.listeners = listeners;
+        state.dispatching = false;
+        drop(state);
+        resume_panic(panic);
+    }
+}
+
+/// Tracks changes to data owned by several handles within one thread and
+/// notifies listeners.
+///
+/// This is the single-threaded counterpart of
+/// [`SharedTracker`](./struct.SharedTracker.html): the data lives behind an
+/// `Rc<RefCell<..>>` rather than an `Arc<Mutex<..>>`, so a `TrackerCell` is
+/// not `Send` but is also available with the `no_std` feature. Cloning a
+/// `TrackerCell` gives another handle to the same data and listeners, and
+/// the listeners fire on a change made through any handle.
+///
+/// Listeners are called after the borrow has been released, so they may
+/// themselves read or modify the cell. Changes made while listeners are being
+/// called are queued and passed to the listeners in order. Listeners added or
+/// removed while listeners are being called take effect once all queued
+/// changes have been delivered.
+///
+/// As with every tracker, the listeners must be `Send` unless the `no_send`
+/// feature is enabled. A `TrackerCell` is not `Send`, so a listener can only
+/// capture a handle to a cell, for example to modify the cell it observes,
+/// with the `no_send` feature.
+pub struct TrackerCell<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    state: Rc<RefCell<State<T, K>>>,
+}
+
+impl<T, K> TrackerCell<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    /// Create a new `TrackerCell` which takes ownership
+    /// of the data of type `T`.
+    pub fn new(value: T) -> TrackerCell<T, K> {
+        TrackerCell { state: Rc::new(RefCell::new(State::new(value))) }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.borrow().inner.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned. While listeners are
+    /// being called, the removal is deferred and `None` is returned unless
+    /// the callback was added during that time.
+    pub fn remove_listener(&self, key: &K) -> Option<Box<dyn OnChanged<T>>>
+        where K: Clone
+    {
+        self.borrow().remove_listener(key)
+    }
+
+    /// Return a copy of the current data.
+    pub fn get(&self) -> T {
+        self.borrow().inner.value.clone()
+    }
+
+    /// Replace the data with `value`, notifying the listeners if it changed.
+    pub fn set(&self, value: T) {
+        self.with_mut(|v| *v = value);
+    }
+
+    /// Modify the data by calling `f` and return its result.
+    ///
+    /// If the data changed, the listeners are called after `f` returns and
+    /// before `with_mut()` returns, unless they are already being called (in
+    /// which case the change is queued). Using the cell from within `f`
+    /// panics, since it is borrowed.
+    pub fn with_mut<F, R>(&self, f: F) -> R
+        where F: FnOnce(&mut T) -> R
+    {
+        State::modify(self.borrow(), || self.borrow(), f)
+    }
+
+    fn borrow(&self) -> RefMut<'_, State<T, K>> {
+        self.state.borrow_mut()
+    }
+}
+
+impl<T, K> Clone for TrackerCell<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    fn clone(&self) -> TrackerCell<T, K> {
+        TrackerCell { state: self.state.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::TrackerCell;
+
+    #[test]
+    fn shared_between_handles() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let cell = TrackerCell::new(0);
+        let other = cell.clone();
+
+        let seen2 = seen.clone();
+        cell.add_listener(0,
+                          move |old_value: &i32, new_value: &i32| {
+                              seen2.lock().unwrap().push((*old_value, *new_value));
+                          });
+        let seen3 = seen.clone();
+        other.add_listener(1,
+                           move |_: &i32, new_value: &i32| {
+                               seen3.lock().unwrap().push((-1, *new_value));
+                           });
+
+        other.set(1);
+        cell.with_mut(|v| *v += 1);
+        assert!(cell.get() == 2 && other.get() == 2);
+        assert!(*seen.lock().unwrap() == vec![(0, 1), (-1, 1), (1, 2), (-1, 2)]);
+    }
+
+    // Listeners must be Send unless no_send is enabled, which a cell is not,
+    // see the documentation of TrackerCell.
+    #[test]
+    #[cfg(feature = "no_send")]
+    fn reentrant_listener() {
+        let cell = TrackerCell::new(0);
+        let cell2 = cell.clone();
+        cell.add_listener(0,
+                          move |_: &i32, new_value: &i32| if *new_value < 3 {
+                              cell2.set(*new_value + 1);
+                          });
+        cell.set(1);
+        assert!(cell.get() == 3);
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Asynchronous delivery of change events.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use super::{MaybeSend, OnChanged};
+
+struct State<T> {
+    queue: VecDeque<(T, T)>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// The listener side of a `Changes` channel.
+pub struct ChangeSender<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> OnChanged<T> for ChangeSender<T>
+    where T: Clone + MaybeSend
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let mut state = self.state.lock().unwrap();
+        state.queue.push_back((old_value.clone(), new_value.clone()));
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for ChangeSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// An asynchronous sequence of `(old_value, new_value)` change events.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::changes()`](./struct.DataTracker.html#method.changes).
+/// The sequence ends once the listener feeding it is dropped, which happens
+/// when the `DataTracker` is dropped or the listener is removed.
+pub struct Changes<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Changes<T> {
+    /// Return the next change if one is queued, `Poll::Ready(None)` once no
+    /// more changes can arrive, or `Poll::Pending` after arranging for the
+    /// task of `cx` to be woken up by the next change.
+    pub fn poll_change(&mut self, cx: &mut Context<'_>) -> Poll<Option<(T, T)>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(change) = state.queue.pop_front() {
+            Poll::Ready(Some(change))
+        } else if state.closed {
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Wait for the next change.
+    ///
+    /// The returned future resolves to `None` once no more changes can arrive.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Next<'_, T> {
+        Next { changes: self }
+    }
+}
+
+/// Future returned by [`Changes::next()`](./struct.Changes.html#method.next).
+pub struct Next<'a, T: 'a> {
+    changes: &'a mut Changes<T>,
+}
+
+impl<'a, T> Future for Next<'a, T> {
+    type Output = Option<(T, T)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<(T, T)>> {
+        self.changes.poll_change(cx)
+    }
+}
+
+/// Create a connected listener and `Changes` pair.
+pub fn channel<T>() -> (ChangeSender<T>, Changes<T>) {
+    let state = Arc::new(Mutex::new(State {
+        queue: VecDeque::new(),
+        waker: None,
+        closed: false,
+    }));
+    (ChangeSender { state: state.clone() }, Changes { state })
+}
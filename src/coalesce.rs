@@ -0,0 +1,75 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Listeners which collapse changes until they are flushed.
+
+use std::sync::{Arc, Mutex};
+
+use super::{Equality, MaybeSend, OnChanged};
+
+/// The listener side of a `Coalescer`, registered with the tracker.
+pub struct CoalescingSender<T> {
+    pending: Arc<Mutex<Option<(T, T)>>>,
+}
+
+pub fn coalescing<T>(callback: Box<dyn OnChanged<T>>,
+                     eq: Equality<T>)
+                     -> (CoalescingSender<T>, Coalescer<T>) {
+    let pending = Arc::new(Mutex::new(None));
+    let sender = CoalescingSender { pending: pending.clone() };
+    (sender, Coalescer { pending, callback, eq })
+}
+
+impl<T> OnChanged<T> for CoalescingSender<T>
+    where T: Clone + MaybeSend
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let mut pending = self.pending.lock().unwrap();
+        match *pending {
+            Some((_, ref mut pending_new)) => *pending_new = new_value.clone(),
+            None => *pending = Some((old_value.clone(), new_value.clone())),
+        }
+    }
+}
+
+/// Collects changes and passes them on to a callback in a single call to
+/// `flush()`.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::add_coalescing_listener()`](./struct.DataTracker.html#method.add_coalescing_listener).
+pub struct Coalescer<T> {
+    pending: Arc<Mutex<Option<(T, T)>>>,
+    callback: Box<dyn OnChanged<T>>,
+    /// The equality of the tracker, to drop changes which cancelled out.
+    eq: Equality<T>,
+}
+
+impl<T> Coalescer<T> {
+    /// Return `true` if changes were collected since the last flush.
+    pub fn has_pending(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
+    }
+
+    /// Pass the collected changes to the callback and start over.
+    ///
+    /// The callback receives the old value of the first and the new value of
+    /// the last change since the previous flush. If there were no changes, or
+    /// they cancelled each other out according to the equality of the
+    /// tracker, the callback is not called. Returns
+    /// `true` if the callback was called.
+    pub fn flush(&self) -> bool {
+        let pending = self.pending.lock().unwrap().take();
+        match pending {
+            Some((old_value, new_value)) if !self.eq.equal(&old_value, &new_value) => {
+                self.callback.on_changed(&old_value, &new_value);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Modifications which tell the listeners why the data changed.
+
+use alloc::boxed::Box;
+use std::any::{self, Any};
+use std::marker::PhantomData;
+
+use super::{sealed, DataTracker, ListenerError, ListenerKey, MaybeSend, Modifier, OnChanged,
+            Propagation};
+
+/// Passes the context of a change to a callback taking `(&C, &T, &T)`.
+struct ContextListener<C, F> {
+    callback: F,
+    context: PhantomData<fn(&C)>,
+}
+
+impl<C, F, T> OnChanged<T> for ContextListener<C, F>
+    where C: Any + Default,
+          F: Fn(&C, &T, &T) + MaybeSend
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        (self.callback)(&C::default(), old_value, new_value)
+    }
+
+    fn handle_in_context(&self,
+                         context: Option<&dyn Any>,
+                         old_value: &T,
+                         new_value: &T,
+                         _: sealed::Token)
+                         -> Result<Propagation, ListenerError> {
+        match context {
+            Some(context) => {
+                let context = match context.downcast_ref::<C>() {
+                    Some(context) => context,
+                    None => {
+                        panic!("a context listener expecting `{}` was passed another context",
+                               any::type_name::<C>())
+                    }
+                };
+                (self.callback)(context, old_value, new_value)
+            }
+            None => self.on_changed(old_value, new_value),
+        }
+        Ok(Propagation::Continue)
+    }
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Add a callback which also receives the context of the change.
+    ///
+    /// The callback is called with the context passed to
+    /// `as_tracked_mut_with_context()`, or with `C::default()` for changes
+    /// made otherwise. All contexts passed to this tracker must be of type
+    /// `C`: a change made with a context of another type panics once all
+    /// listeners have been called.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_context_listener<C, F>(&mut self,
+                                      key: K,
+                                      callback: F)
+                                      -> Option<Box<dyn OnChanged<T>>>
+        where C: Any + Default,
+              F: Fn(&C, &T, &T) + MaybeSend + 'static
+    {
+        let listener = ContextListener {
+            callback,
+            context: PhantomData,
+        };
+        self.inner.listeners.insert(key, 0, false, Box::new(listener))
+    }
+
+    /// Return a `ContextModifier` which can be used to modify the owned data,
+    /// reporting `context` as the reason of the change.
+    ///
+    /// This behaves like `as_tracked_mut()`. When the `ContextModifier` goes
+    /// out of scope and the data changed, callbacks added with
+    /// `add_context_listener()` receive `context`. Other callbacks are called
+    /// as if the change was made with `as_tracked_mut()`. The context is
+    /// dropped after the notification.
+    pub fn as_tracked_mut_with_context<C>(&mut self, context: C) -> ContextModifier<'_, T, K, C>
+        where C: Any
+    {
+        ContextModifier {
+            modifier: Modifier::new(&mut self.inner),
+            context: Some(context),
+        }
+    }
+}
+
+/// Allow modifying data owned by `DataTracker` while telling the listeners
+/// why it changed.
+///
+/// This wraps a [`Modifier`](./struct.Modifier.html), which detects the
+/// change. Create an instance of this by calling
+/// [`DataTracker::as_tracked_mut_with_context()`](./struct.DataTracker.html#method.as_tracked_mut_with_context).
+pub struct ContextModifier<'a, T, K, C>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey,
+          C: Any
+{
+    modifier: Modifier<'a, T, K>,
+    context: Option<C>,
+}
+
+impl<'a, T, K, C> ContextModifier<'a, T, K, C>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey,
+          C: Any
+{
+    /// Check for a change and notify the listeners now, rather than on drop.
+    ///
+    /// Returns `true` if the data changed and the listeners were called.
+    pub fn commit(mut self) -> bool {
+        self.finish()
+    }
+
+    /// Like `Modifier::nested()`, the context is reported once, when this
+    /// `ContextModifier` is dropped.
+    pub fn nested(&mut self) -> Modifier<'_, T, K> {
+        self.modifier.nested()
+    }
+
+    /// See `Modifier::mark_dirty()`.
+    pub fn mark_dirty(&mut self) {
+        self.modifier.mark_dirty();
+    }
+
+    /// See `Modifier::mark_clean()`.
+    pub fn mark_clean(&mut self) {
+        self.modifier.mark_clean();
+    }
+
+    fn finish(&mut self) -> bool {
+        match self.context.take() {
+            Some(context) => self.modifier.finish(Some(&context), false).0,
+            None => false,
+        }
+    }
+}
+
+impl<'a, T, K, C> std::ops::Deref for ContextModifier<'a, T, K, C>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey,
+          C: Any
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.modifier
+    }
+}
+
+impl<'a, T, K, C> std::ops::DerefMut for ContextModifier<'a, T, K, C>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey,
+          C: Any
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.modifier
+    }
+}
+
+impl<'a, T, K, C> Drop for ContextModifier<'a, T, K, C>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey,
+          C: Any
+{
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::super::DataTracker;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Default)]
+    enum Reason {
+        #[default]
+        Unknown,
+        User,
+    }
+
+    #[test]
+    fn context_reaches_listeners() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.add_context_listener(0,
+                                          move |reason: &Reason, old_value: &i32, new_value: &i32| {
+                                              let change = (*reason, *old_value, *new_value);
+                                              seen2.lock().unwrap().push(change);
+                                          });
+        let seen3 = seen.clone();
+        tracked_data.add_listener(1, move |old_value: &i32, new_value: &i32| {
+            seen3.lock().unwrap().push((Reason::Unknown, -old_value, -new_value));
+        });
+
+        *tracked_data.as_tracked_mut_with_context(Reason::User) = 2;
+        {
+            // Not modified, so not reported.
+            let _x = tracked_data.as_tracked_mut_with_context(Reason::User);
+        }
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() ==
+                vec![(Reason::User, 1, 2),
+                     (Reason::Unknown, -1, -2),
+                     (Reason::Unknown, 2, 3),
+                     (Reason::Unknown, -2, -3)]);
+    }
+
+    #[test]
+    fn context_modifier_hints() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.add_context_listener(0, move |reason: &Reason, _: &i32, new_value: &i32| {
+            seen2.lock().unwrap().push((*reason, *new_value));
+        });
+
+        {
+            let mut x = tracked_data.as_tracked_mut_with_context(Reason::User);
+            *x.nested() = 2;
+            x.mark_clean();
+        }
+        tracked_data.as_tracked_mut_with_context(Reason::User).mark_dirty();
+        assert!(*seen.lock().unwrap() == vec![(Reason::User, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "a context listener expecting")]
+    fn context_type_mismatch() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_context_listener(0, |_: &Reason, _: &i32, _: &i32| {});
+        *tracked_data.as_tracked_mut_with_context("user") = 2;
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Notification with a computed difference rather than old and new values.
+
+use alloc::boxed::Box;
+
+use super::{ListenerKey, Listeners, MaybeSend};
+
+/// Trait defining a change notification callback function which receives
+/// the new value and the computed difference of type `D`.
+#[cfg(not(feature = "no_send"))]
+pub trait DiffOnChanged<T, D>: Send {
+    fn on_changed(&self, new_value: &T, diff: &D);
+}
+
+#[cfg(feature = "no_send")]
+pub trait DiffOnChanged<T, D> {
+    fn on_changed(&self, new_value: &T, diff: &D);
+}
+
+#[cfg(not(feature = "no_send"))]
+impl<F, T, D> DiffOnChanged<T, D> for F
+    where F: Fn(&T, &D) + Send
+{
+    fn on_changed(&self, new_value: &T, diff: &D) {
+        self(new_value, diff)
+    }
+}
+
+#[cfg(feature = "no_send")]
+impl<F, T, D> DiffOnChanged<T, D> for F
+    where F: Fn(&T, &D)
+{
+    fn on_changed(&self, new_value: &T, diff: &D) {
+        self(new_value, diff)
+    }
+}
+
+impl<T, D> DiffOnChanged<T, D> for Box<dyn DiffOnChanged<T, D>> {
+    fn on_changed(&self, new_value: &T, diff: &D) {
+        (**self).on_changed(new_value, diff)
+    }
+}
+
+#[cfg(not(feature = "no_send"))]
+type DiffFn<T, D> = Box<dyn Fn(&T, &T) -> D + Send>;
+
+#[cfg(feature = "no_send")]
+type DiffFn<T, D> = Box<dyn Fn(&T, &T) -> D>;
+
+/// Tracks changes to data and notifies listeners with a computed difference.
+///
+/// This works like [`DataTracker`](./struct.DataTracker.html), but when a
+/// change is detected, a user supplied function computes a value of type `D`
+/// describing the change from the old and the new values. That value is then
+/// passed to every listener, so listeners do not each need to work out what
+/// changed. The diff function is only called when a change was detected.
+pub struct DiffTracker<T, D, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    value: T,
+    diff: DiffFn<T, D>,
+    listeners: Listeners<dyn DiffOnChanged<T, D>, K>,
+}
+
+impl<T, D, K> DiffTracker<T, D, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    /// Create a new `DiffTracker` which takes ownership of the data of type
+    /// `T` and describes changes using `diff(old_value, new_value)`.
+    pub fn new<F>(value: T, diff: F) -> DiffTracker<T, D, K>
+        where F: Fn(&T, &T) -> D + MaybeSend + 'static
+    {
+        DiffTracker {
+            value,
+            diff: Box::new(diff),
+            listeners: Listeners::new(),
+        }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&mut self, key: K, callback: C) -> Option<Box<dyn DiffOnChanged<T, D>>>
+        where C: DiffOnChanged<T, D> + 'static
+    {
+        self.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    pub fn remove_listener(&mut self, key: &K) -> Option<Box<dyn DiffOnChanged<T, D>>> {
+        self.listeners.remove(key)
+    }
+
+    /// Return a `DiffModifier` which can be used to modify the owned data.
+    pub fn as_tracked_mut(&mut self) -> DiffModifier<'_, T, D, K> {
+        let orig_copy = if self.listeners.fn_map.is_empty() {
+            None
+        } else {
+            Some(self.value.clone())
+        };
+        DiffModifier {
+            orig_copy,
+            tracker: self,
+        }
+    }
+
+    /// Consume the `DiffTracker` and return the owned data.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, D, K> AsRef<T> for DiffTracker<T, D, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Allow viewing and modifying data owned by `DiffTracker`.
+///
+/// Create an instance of this by calling
+/// [`DiffTracker::as_tracked_mut()`](./struct.DiffTracker.html#method.as_tracked_mut).
+pub struct DiffModifier<'a, T, D: 'a, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    orig_copy: Option<T>,
+    tracker: &'a mut DiffTracker<T, D, K>,
+}
+
+impl<'a, T, D, K> std::ops::Deref for DiffModifier<'a, T, D, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tracker.value
+    }
+}
+
+impl<'a, T, D, K> std::ops::DerefMut for DiffModifier<'a, T, D, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.tracker.value
+    }
+}
+
+impl<'a, T, D, K> Drop for DiffModifier<'a, T, D, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        if let Some(orig_copy) = self.orig_copy.take() {
+            let tracker = &mut *self.tracker;
+            if orig_copy != tracker.value {
+                let diff = (tracker.diff)(&orig_copy, &tracker.value);
+                let new_value = &tracker.value;
+                tracker.listeners.dispatch(|callback| {
+                    callback.on_changed(new_value, &diff);
+                    Ok(())
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::DiffTracker;
+
+    #[test]
+    fn track_diff() {
+        #[derive(Clone, PartialEq)]
+        struct Settings {
+            volume: u8,
+            muted: bool,
+        }
+
+        #[derive(Debug, PartialEq)]
+        enum SettingsChange {
+            Volume,
+            Muted,
+        }
+
+        let diff_count = Arc::new(Mutex::new(0));
+        let dc2 = diff_count.clone();
+        let diff = move |old: &Settings, new: &Settings| {
+            *dc2.lock().unwrap() += 1;
+            let mut changes = Vec::new();
+            if old.volume != new.volume {
+                changes.push(SettingsChange::Volume);
+            }
+            if old.muted != new.muted {
+                changes.push(SettingsChange::Muted);
+            }
+            changes
+        };
+        let settings = Settings {
+            volume: 5,
+            muted: false,
+        };
+        let mut tracked_data = DiffTracker::new(settings, diff);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |new_value: &Settings, diff: &Vec<SettingsChange>| {
+                                      assert!(new_value.muted);
+                                      seen2.lock().unwrap().extend(diff.iter().map(|c| format!("{:?}", c)));
+                                  });
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            x.muted = true;
+        }
+        assert!(*seen.lock().unwrap() == vec!["Muted".to_string()]);
+
+        // The diff function only runs for actual changes.
+        drop(tracked_data.as_tracked_mut());
+        assert!(*diff_count.lock().unwrap() == 1);
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Field-level change tracking.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::{resume_panic, ListenerKey, Listeners, OnChanged};
+
+/// Types whose fields can be tracked individually by a
+/// [`FieldTracker`](./struct.FieldTracker.html).
+///
+/// With the `derive` feature, this can be derived with
+/// `#[derive(TrackFields)]` for structs with named fields. See the
+/// `data_tracker_derive` crate for details.
+pub trait TrackFields {
+    /// Identifies a tracked field.
+    type Field: Copy + Hash + Eq;
+
+    /// Return the tracked fields which differ between `old` and `new`.
+    fn changed_fields(old: &Self, new: &Self) -> Vec<Self::Field>;
+}
+
+/// Tracks changes to individual fields of data and notifies listeners.
+///
+/// This works like [`DataTracker`](./struct.DataTracker.html), but each
+/// listener subscribes to a single field of `T`. When a change is detected,
+/// only the listeners of the fields that changed are called. They receive
+/// references to the whole old and new values.
+pub struct FieldTracker<T, K>
+    where T: TrackFields + Clone + PartialEq,
+          K: ListenerKey
+{
+    value: T,
+    fields: HashMap<T::Field, Listeners<dyn OnChanged<T>, K>>,
+}
+
+impl<T, K> FieldTracker<T, K>
+    where T: TrackFields + Clone + PartialEq,
+          K: ListenerKey
+{
+    /// Create a new `FieldTracker` which takes ownership
+    /// of the data of type `T`.
+    pub fn new(value: T) -> FieldTracker<T, K> {
+        FieldTracker {
+            value,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Add a callback that will be called just after a change of `field` is
+    /// detected.
+    ///
+    /// Each field has its own set of keys. If a previous callback exists with
+    /// the `key` for this `field`, the original callback is returned as
+    /// `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&mut self,
+                           field: T::Field,
+                           key: K,
+                           callback: C)
+                           -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.fields
+            .entry(field)
+            .or_insert_with(Listeners::new)
+            .insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback of `field`.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    pub fn remove_listener(&mut self, field: T::Field, key: &K) -> Option<Box<dyn OnChanged<T>>> {
+        self.fields.get_mut(&field).and_then(|listeners| listeners.remove(key))
+    }
+
+    /// Return a `FieldModifier` which can be used to modify the owned data.
+    pub fn as_tracked_mut(&mut self) -> FieldModifier<'_, T, K> {
+        let has_listeners = self.fields.values().any(|l| !l.fn_map.is_empty());
+        let orig_copy = if has_listeners {
+            Some(self.value.clone())
+        } else {
+            None
+        };
+        FieldModifier {
+            orig_copy,
+            tracker: self,
+        }
+    }
+
+    /// Consume the `FieldTracker` and return the owned data.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, K> AsRef<T> for FieldTracker<T, K>
+    where T: TrackFields + Clone + PartialEq,
+          K: ListenerKey
+{
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Allow viewing and modifying data owned by `FieldTracker`.
+///
+/// Create an instance of this by calling
+/// [`FieldTracker::as_tracked_mut()`](./struct.FieldTracker.html#method.as_tracked_mut).
+pub struct FieldModifier<'a, T, K>
+    where T: 'a + TrackFields + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    orig_copy: Option<T>,
+    tracker: &'a mut FieldTracker<T, K>,
+}
+
+impl<'a, T, K> std::ops::Deref for FieldModifier<'a, T, K>
+    where T: 'a + TrackFields + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tracker.value
+    }
+}
+
+impl<'a, T, K> std::ops::DerefMut for FieldModifier<'a, T, K>
+    where T: 'a + TrackFields + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.tracker.value
+    }
+}
+
+impl<'a, T, K> Drop for FieldModifier<'a, T, K>
+    where T: 'a + TrackFields + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        if let Some(orig_copy) = self.orig_copy.take() {
+            let tracker = &mut *self.tracker;
+            if orig_copy != tracker.value {
+                let mut panic = None;
+                for field in T::changed_fields(&orig_copy, &tracker.value) {
+                    if let Some(listeners) = tracker.fields.get_mut(&field) {
+                        let (_, p) = listeners.notify_catching(&orig_copy, &tracker.value);
+                        panic = panic.or(p);
+                    }
+                }
+                resume_panic(panic);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Accumulators updated with every change.
+
+use alloc::boxed::Box;
+use std::any::Any;
+use std::borrow::Borrow;
+
+use super::{DataTracker, Exclusive, ListenerKey, MaybeSend, MaybeSync};
+
+/// An accumulator and the function folding each change into it.
+///
+/// The accumulator is handed out by `fold_state()`, so it must be `Sync`.
+pub trait Fold<T>: MaybeSend + MaybeSync {
+    fn update(&mut self, old_value: &T, new_value: &T);
+    fn state(&self) -> &dyn Any;
+}
+
+struct FoldListener<A, F> {
+    acc: A,
+    f: Exclusive<F>,
+}
+
+impl<A, F, T> Fold<T> for FoldListener<A, F>
+    where A: Any + MaybeSend + MaybeSync,
+          F: Fn(&mut A, &T, &T) + MaybeSend
+{
+    fn update(&mut self, old_value: &T, new_value: &T) {
+        (self.f.get_mut())(&mut self.acc, old_value, new_value)
+    }
+
+    fn state(&self) -> &dyn Any {
+        &self.acc
+    }
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Add an accumulator which starts at `init` and is updated with every
+    /// change by calling `f(&mut accumulator, old_value, new_value)`.
+    ///
+    /// The current accumulator is available from `fold_state()`. Since the
+    /// tracker owns the accumulator, no locking is needed to read it. The
+    /// accumulators are updated before the listeners are called. They have
+    /// keys of their own, separate from those of the callbacks.
+    ///
+    /// If a previous accumulator exists with the `key`, it is replaced and
+    /// `true` is returned.
+    pub fn add_fold_listener<A, F>(&mut self, key: K, init: A, f: F) -> bool
+        where A: Any + MaybeSend + MaybeSync,
+              F: Fn(&mut A, &T, &T) + MaybeSend + 'static
+    {
+        let fold = FoldListener {
+            acc: init,
+            f: Exclusive(f),
+        };
+        self.inner.folds.insert(key, Box::new(fold)).is_some()
+    }
+
+    /// Return the current accumulator added with the `key`.
+    ///
+    /// Returns `None` if there is no such accumulator or it is not of type
+    /// `A`.
+    pub fn fold_state<A, Q>(&self, key: &Q) -> Option<&A>
+        where A: Any,
+              K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.inner.folds.get(key).and_then(|fold| fold.state().downcast_ref())
+    }
+
+    /// Remove the accumulator added with the `key`.
+    ///
+    /// Returns `true` if there was such an accumulator.
+    pub fn remove_fold_listener<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.inner.folds.remove(key).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::DataTracker;
+
+    #[test]
+    fn running_accumulation() {
+        let mut tracked_data: DataTracker<i32, &str> = DataTracker::new(1);
+        tracked_data.add_fold_listener("delta",
+                                       0,
+                                       |sum: &mut i32, old_value: &i32, new_value: &i32| {
+                                           *sum += new_value - old_value;
+                                       });
+        tracked_data.add_fold_listener("count", 0usize, |count: &mut usize, _: &i32, _: &i32| {
+            *count += 1;
+        });
+
+        tracked_data.set(4);
+        *tracked_data.as_tracked_mut() -= 1;
+        tracked_data.set(3);
+        assert!(tracked_data.fold_state::<i32, _>("delta") == Some(&2));
+        assert!(tracked_data.fold_state::<usize, _>("count") == Some(&2));
+        assert!(tracked_data.fold_state::<i32, _>("count").is_none());
+
+        assert!(tracked_data.remove_fold_listener("count"));
+        assert!(tracked_data.fold_state::<usize, _>("count").is_none());
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Read access which can be counted to find aliasing bugs.
+
+#[cfg(feature = "debug_checks")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{DataTracker, Inner, ListenerKey};
+
+impl<T, K> Inner<T, K>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Panic in debug builds if a `ReadGuard` exists, before mutable access
+    /// to the data is granted.
+    #[cfg(feature = "debug_checks")]
+    pub fn check_readers(&mut self) {
+        debug_assert!(*self.readers.get_mut() == 0,
+                      "the data was borrowed mutably while a ReadGuard exists");
+    }
+
+    #[cfg(not(feature = "debug_checks"))]
+    pub fn check_readers(&mut self) {}
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Return a `ReadGuard` giving read access to the owned data.
+    ///
+    /// This is like `as_ref()`, except that with the `debug_checks` feature,
+    /// the tracker counts the outstanding guards, and any method granting
+    /// mutable access to the data, such as `as_tracked_mut()`, `set()` or
+    /// `as_mut()`, panics in debug builds while one exists. This is a diagnostic aid to
+    /// catch reads overlapping with writes, for example when raw pointers or
+    /// other unsafe code defeat the borrow checker within a graph of
+    /// callbacks. It is not a safety mechanism. Without the feature, the
+    /// guard is a plain reference.
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        #[cfg(feature = "debug_checks")]
+        self.inner.readers.fetch_add(1, Ordering::SeqCst);
+        ReadGuard {
+            value: &self.inner.value,
+            #[cfg(feature = "debug_checks")]
+            readers: &self.inner.readers,
+        }
+    }
+}
+
+/// Read access to data owned by `DataTracker`.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::read()`](./struct.DataTracker.html#method.read).
+pub struct ReadGuard<'a, T: 'a> {
+    value: &'a T,
+    #[cfg(feature = "debug_checks")]
+    readers: &'a AtomicUsize,
+}
+
+impl<'a, T> std::ops::Deref for ReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+#[cfg(feature = "debug_checks")]
+impl<'a, T> Drop for ReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::DataTracker;
+
+    #[test]
+    fn read_guard() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        {
+            let a = tracked_data.read();
+            let b = tracked_data.read();
+            assert!(*a + *b == 2);
+        }
+        // All guards are gone, so modifying is fine.
+        *tracked_data.as_tracked_mut() = 2;
+        assert!(*tracked_data.read() == 2);
+    }
+
+    #[cfg(feature = "debug_checks")]
+    #[test]
+    #[should_panic(expected = "ReadGuard exists")]
+    fn modifier_during_read() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        // A raw pointer defeats the borrow checker, as in the unsafe code
+        // the check is meant to catch.
+        let tracker: *mut DataTracker<i32, u8> = &mut tracked_data;
+        let value = unsafe { (*tracker).read() };
+        let mut x = unsafe { (*tracker).as_tracked_mut() };
+        *x = *value + 1;
+    }
+
+    #[cfg(feature = "debug_checks")]
+    #[test]
+    #[should_panic(expected = "ReadGuard exists")]
+    fn set_during_read() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        let tracker: *mut DataTracker<i32, u8> = &mut tracked_data;
+        let value = unsafe { (*tracker).read() };
+        unsafe { (*tracker).set(*value + 1) };
+    }
+}
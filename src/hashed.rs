@@ -0,0 +1,182 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Change detection by hashing rather than copying.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::{resume_panic, ListenerKey, Listeners, OnChanged};
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Tracks changes to data by comparing hashes and notifies listeners.
+///
+/// This works like [`DataTracker`](./struct.DataTracker.html), but instead of
+/// copying the data when a [`HashModifier`](./struct.HashModifier.html) is
+/// created, only a 64-bit hash of the data is stored. When the modifier is
+/// dropped, the data is hashed again and the listeners are called if the two
+/// hashes differ. This avoids cloning large values, and `T` needs neither
+/// `Clone` nor `PartialEq`.
+///
+/// There are two consequences. First, there is a tiny chance that a change
+/// goes unnoticed because the old and the new data hash to the same value.
+/// Second, the old value is not available, so listeners are called with a
+/// reference to the new value as both their old and their new argument.
+pub struct HashTracker<T, K>
+    where T: Hash,
+          K: ListenerKey
+{
+    value: T,
+    listeners: Listeners<dyn OnChanged<T>, K>,
+}
+
+impl<T, K> HashTracker<T, K>
+    where T: Hash,
+          K: ListenerKey
+{
+    /// Create a new `HashTracker` which takes ownership
+    /// of the data of type `T`.
+    pub fn new(value: T) -> HashTracker<T, K> {
+        HashTracker {
+            value,
+            listeners: Listeners::new(),
+        }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&mut self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    pub fn remove_listener(&mut self, key: &K) -> Option<Box<dyn OnChanged<T>>> {
+        self.listeners.remove(key)
+    }
+
+    /// Return a `HashModifier` which can be used to modify the owned data.
+    pub fn as_tracked_mut(&mut self) -> HashModifier<'_, T, K> {
+        let orig_hash = hash_of(&self.value);
+        HashModifier {
+            orig_hash,
+            tracker: self,
+        }
+    }
+
+    /// Consume the `HashTracker` and return the owned data.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, K> AsRef<T> for HashTracker<T, K>
+    where T: Hash,
+          K: ListenerKey
+{
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Allow viewing and modifying data owned by `HashTracker`.
+///
+/// Create an instance of this by calling
+/// [`HashTracker::as_tracked_mut()`](./struct.HashTracker.html#method.as_tracked_mut).
+pub struct HashModifier<'a, T, K>
+    where T: 'a + Hash,
+          K: 'a + ListenerKey
+{
+    orig_hash: u64,
+    tracker: &'a mut HashTracker<T, K>,
+}
+
+impl<'a, T, K> std::ops::Deref for HashModifier<'a, T, K>
+    where T: 'a + Hash,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tracker.value
+    }
+}
+
+impl<'a, T, K> std::ops::DerefMut for HashModifier<'a, T, K>
+    where T: 'a + Hash,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.tracker.value
+    }
+}
+
+impl<'a, T, K> Drop for HashModifier<'a, T, K>
+    where T: 'a + Hash,
+          K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        if hash_of(&self.tracker.value) != self.orig_hash {
+            let tracker = &mut *self.tracker;
+            let (_, panic) = tracker.listeners.notify_catching(&tracker.value, &tracker.value);
+            resume_panic(panic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::HashTracker;
+
+    #[test]
+    fn track_by_hash() {
+        // Neither Clone nor PartialEq is required.
+        #[derive(Hash)]
+        struct MyData {
+            a: Vec<u8>,
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = HashTracker::new(MyData { a: vec![1] });
+
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &MyData, new_value: &MyData| {
+                                      assert!(old_value.a == new_value.a);
+                                      let data = &mut *cc2.lock().unwrap();
+                                      *data += 1;
+                                  });
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            x.a.push(2);
+        }
+        assert!(*change_count.lock().unwrap() == 1);
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            x.a.push(3);
+            x.a.pop();
+        }
+        assert!(*change_count.lock().unwrap() == 1);
+
+        assert!(tracked_data.into_inner().a == vec![1, 2]);
+    }
+}
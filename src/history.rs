@@ -0,0 +1,256 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Change tracking with undo and redo.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use super::{Inner, ListenerKey, OnChanged};
+
+/// Tracks changes to data, notifies listeners and keeps a history of previous
+/// values.
+///
+/// This works like [`DataTracker`](./struct.DataTracker.html), but every
+/// detected change pushes the old value onto an undo stack. `undo()` restores
+/// the previous value and `redo()` reapplies an undone value, and both notify
+/// the listeners like any other change. Making a new change after an undo
+/// discards the values which could have been redone.
+///
+/// At most `max_history` old values are kept. When the limit is reached, the
+/// oldest value is dropped.
+pub struct HistoryTracker<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    inner: Inner<T, K>,
+    undo_stack: VecDeque<T>,
+    redo_stack: Vec<T>,
+    max_history: usize,
+}
+
+impl<T, K> HistoryTracker<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    /// Create a new `HistoryTracker` which takes ownership of the data of type
+    /// `T` and remembers up to `max_history` previous values.
+    pub fn new(value: T, max_history: usize) -> HistoryTracker<T, K> {
+        HistoryTracker {
+            inner: Inner::new(value),
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            max_history,
+        }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&mut self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.inner.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    pub fn remove_listener(&mut self, key: &K) -> Option<Box<dyn OnChanged<T>>> {
+        self.inner.listeners.remove(key)
+    }
+
+    /// Replace the owned data with `value`.
+    ///
+    /// If the new value is not equal to the previous value, the previous value
+    /// is recorded in the history and the listeners are called.
+    pub fn set(&mut self, value: T) {
+        let old_value = std::mem::replace(&mut self.inner.value, value);
+        self.record(old_value);
+    }
+
+    /// Return a `HistoryModifier` which can be used to modify the owned data.
+    ///
+    /// Unlike `DataTracker`, a copy of the data is always made, since it is
+    /// needed for the history even without listeners.
+    pub fn as_tracked_mut(&mut self) -> HistoryModifier<'_, T, K> {
+        HistoryModifier {
+            orig_copy: Some(self.inner.value.clone()),
+            tracker: self,
+        }
+    }
+
+    /// Restore the value before the most recent change.
+    ///
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop_back() {
+            Some(value) => {
+                let old_value = std::mem::replace(&mut self.inner.value, value);
+                self.inner.notify_listeners(&old_value);
+                self.redo_stack.push(old_value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapply the most recently undone value.
+    ///
+    /// Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(value) => {
+                let old_value = std::mem::replace(&mut self.inner.value, value);
+                self.inner.notify_listeners(&old_value);
+                self.push_undo(old_value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return `true` if `undo()` would restore a value.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Return `true` if `redo()` would reapply a value.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Consume the `HistoryTracker` and return the owned data.
+    pub fn into_inner(self) -> T {
+        self.inner.value
+    }
+
+    /// If the data differs from `old_value`, start a new branch of history
+    /// and notify the listeners.
+    fn record(&mut self, old_value: T) {
+        if old_value != self.inner.value {
+            self.inner.notify_listeners(&old_value);
+            self.redo_stack.clear();
+            self.push_undo(old_value);
+        }
+    }
+
+    fn push_undo(&mut self, value: T) {
+        if self.max_history == 0 {
+            return;
+        }
+        if self.undo_stack.len() == self.max_history {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(value);
+    }
+}
+
+impl<T, K> AsRef<T> for HistoryTracker<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    fn as_ref(&self) -> &T {
+        &self.inner.value
+    }
+}
+
+/// Allow viewing and modifying data owned by `HistoryTracker`.
+///
+/// Create an instance of this by calling
+/// [`HistoryTracker::as_tracked_mut()`](./struct.HistoryTracker.html#method.as_tracked_mut).
+pub struct HistoryModifier<'a, T, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    orig_copy: Option<T>,
+    tracker: &'a mut HistoryTracker<T, K>,
+}
+
+impl<'a, T, K> std::ops::Deref for HistoryModifier<'a, T, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tracker.inner.value
+    }
+}
+
+impl<'a, T, K> std::ops::DerefMut for HistoryModifier<'a, T, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.tracker.inner.value
+    }
+}
+
+impl<'a, T, K> Drop for HistoryModifier<'a, T, K>
+    where T: 'a + Clone + PartialEq,
+          K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        if let Some(orig_copy) = self.orig_copy.take() {
+            self.tracker.record(orig_copy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::HistoryTracker;
+
+    #[test]
+    fn undo_redo() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = HistoryTracker::new(1, 10);
+
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        tracked_data.set(2);
+        *tracked_data.as_tracked_mut() = 3;
+        assert!(tracked_data.undo());
+        assert!(*tracked_data.as_ref() == 2);
+        assert!(tracked_data.undo());
+        assert!(*tracked_data.as_ref() == 1);
+        assert!(!tracked_data.undo());
+        assert!(tracked_data.redo());
+        assert!(*tracked_data.as_ref() == 2);
+        assert!(*seen.lock().unwrap() == vec![(1, 2), (2, 3), (3, 2), (2, 1), (1, 2)]);
+
+        // A new change discards the redo stack.
+        assert!(tracked_data.can_redo());
+        tracked_data.set(5);
+        assert!(!tracked_data.can_redo());
+        assert!(!tracked_data.redo());
+        assert!(tracked_data.undo());
+        assert!(*tracked_data.as_ref() == 2);
+    }
+
+    #[test]
+    fn max_history() {
+        let mut tracked_data: HistoryTracker<i32, u8> = HistoryTracker::new(0, 2);
+        for i in 1..5 {
+            tracked_data.set(i);
+        }
+        assert!(tracked_data.undo());
+        assert!(tracked_data.undo());
+        assert!(!tracked_data.undo());
+        assert!(*tracked_data.as_ref() == 2);
+    }
+}
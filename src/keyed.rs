@@ -0,0 +1,228 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Change tracking of the entries of a map.
+
+use alloc::boxed::Box;
+use std::borrow::Borrow;
+
+use super::{Inner, ListenerKey, Map, Modifier, OnChanged};
+
+/// Error returned by
+/// [`MapTracker::add_listener()`](./struct.MapTracker.html#method.add_listener)
+/// when there is no entry for the map key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoEntry;
+
+impl std::fmt::Display for NoEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "there is no entry for the map key")
+    }
+}
+
+impl std::error::Error for NoEntry {}
+
+/// Tracks changes to each entry of a map and notifies the listeners of that
+/// entry.
+///
+/// The entries are values of type `T` under keys of type `KM`. Every entry
+/// works like its own [`DataTracker`](./struct.DataTracker.html): callbacks
+/// are registered for a given entry with keys of type `K`, and are only
+/// called when that entry changes. Adding or removing entries notifies
+/// nobody.
+pub struct MapTracker<KM, T, K>
+    where KM: ListenerKey,
+          T: Clone + PartialEq,
+          K: ListenerKey
+{
+    entries: Map<KM, Inner<T, K>>,
+}
+
+impl<KM, T, K> MapTracker<KM, T, K>
+    where KM: ListenerKey,
+          T: Clone + PartialEq,
+          K: ListenerKey
+{
+    /// Create a new, empty `MapTracker`.
+    pub fn new() -> MapTracker<KM, T, K> {
+        MapTracker { entries: Map::new() }
+    }
+
+    /// Set the entry of `map_key` to `value`.
+    ///
+    /// If the entry exists, this behaves like `DataTracker::replace()`: its
+    /// listeners are called if the value changed, and the previous value is
+    /// returned as `Some(previous_value)`. Otherwise, a new entry without
+    /// listeners is created and `None` is returned.
+    pub fn insert(&mut self, map_key: KM, value: T) -> Option<T> {
+        match self.entries.get_mut(&map_key) {
+            Some(inner) => {
+                let old_value = std::mem::replace(&mut inner.value, value);
+                if inner.changed_from(&old_value) {
+                    inner.notify_listeners(&old_value);
+                }
+                Some(old_value)
+            }
+            None => {
+                self.entries.insert(map_key, Inner::new(value));
+                None
+            }
+        }
+    }
+
+    /// Remove the entry of `map_key`, together with its listeners.
+    ///
+    /// Returns the value of the entry as `Some(value)`, or `None` if there is
+    /// no such entry.
+    pub fn remove<Q>(&mut self, map_key: &Q) -> Option<T>
+        where KM: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.entries.remove(map_key).map(|inner| inner.value)
+    }
+
+    /// Return a reference to the value of the entry of `map_key`.
+    pub fn get<Q>(&self, map_key: &Q) -> Option<&T>
+        where KM: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.entries.get(map_key).map(|inner| &inner.value)
+    }
+
+    /// Return a `Modifier` which can be used to modify the entry of
+    /// `map_key`, or `None` if there is no such entry.
+    ///
+    /// When the `Modifier` is dropped, only the listeners of this entry are
+    /// notified of a change.
+    pub fn entry_mut<Q>(&mut self, map_key: &Q) -> Option<Modifier<'_, T, K>>
+        where KM: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.entries.get_mut(map_key).map(Modifier::new)
+    }
+
+    /// Add a callback that will be called just after a change of the entry of
+    /// `map_key`.
+    ///
+    /// If a previous callback exists with the `key` for this entry, the
+    /// original callback is returned as `Ok(Some(original_callback))`.
+    /// Otherwise, `Ok(None)` is returned. If there is no entry for `map_key`,
+    /// the callback is dropped and `Err(NoEntry)` is returned.
+    pub fn add_listener<Q, C>(&mut self,
+                              map_key: &Q,
+                              key: K,
+                              callback: C)
+                              -> Result<Option<Box<dyn OnChanged<T>>>, NoEntry>
+        where KM: Borrow<Q>,
+              Q: ?Sized + ListenerKey,
+              C: OnChanged<T> + 'static
+    {
+        match self.entries.get_mut(map_key) {
+            Some(inner) => Ok(inner.listeners.insert(key, 0, false, Box::new(callback))),
+            None => Err(NoEntry),
+        }
+    }
+
+    /// Remove a callback of the entry of `map_key`.
+    ///
+    /// If a callback exists with the `key` for this entry, it is removed and
+    /// returned as `Some(callback)`. Otherwise, `None` is returned.
+    ///
+    /// Like `HashMap::remove()`, the `key` may be any borrowed form of `K`,
+    /// such as a `&str` for `String` keys.
+    pub fn remove_listener<Q, R>(&mut self, map_key: &Q, key: &R) -> Option<Box<dyn OnChanged<T>>>
+        where KM: Borrow<Q>,
+              Q: ?Sized + ListenerKey,
+              K: Borrow<R>,
+              R: ?Sized + ListenerKey
+    {
+        self.entries.get_mut(map_key).and_then(|inner| inner.listeners.remove(key))
+    }
+
+    /// Return `true` if there is an entry for `map_key`.
+    pub fn contains_key<Q>(&self, map_key: &Q) -> bool
+        where KM: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.entries.contains_key(map_key)
+    }
+
+    /// Return the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return `true` if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<KM, T, K> Default for MapTracker<KM, T, K>
+    where KM: ListenerKey,
+          T: Clone + PartialEq,
+          K: ListenerKey
+{
+    fn default() -> MapTracker<KM, T, K> {
+        MapTracker::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::{MapTracker, NoEntry};
+
+    #[test]
+    fn per_entry_listeners() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked: MapTracker<String, i32, u8> = MapTracker::new();
+        assert!(tracked.insert("a".to_string(), 1).is_none());
+        assert!(tracked.insert("b".to_string(), 2).is_none());
+
+        for map_key in ["a", "b"].iter() {
+            let seen2 = seen.clone();
+            let name = map_key.to_string();
+            let added = tracked.add_listener(*map_key,
+                                             0,
+                                             move |old_value: &i32, new_value: &i32| {
+                                                 let change = (name.clone(),
+                                                               *old_value,
+                                                               *new_value);
+                                                 seen2.lock().unwrap().push(change);
+                                             });
+            assert!(added.unwrap().is_none());
+        }
+
+        *tracked.entry_mut("a").unwrap() += 10;
+        {
+            // Not modified, so not reported.
+            let _x = tracked.entry_mut("b").unwrap();
+        }
+        assert!(tracked.insert("b".to_string(), 3) == Some(2));
+        assert!(tracked.entry_mut("c").is_none());
+        assert!(*seen.lock().unwrap() == vec![("a".to_string(), 1, 11), ("b".to_string(), 2, 3)]);
+
+        assert!(tracked.remove("a") == Some(11));
+        assert!(tracked.len() == 1 && !tracked.contains_key("a"));
+        assert!(*tracked.get("b").unwrap() == 3);
+    }
+
+    #[test]
+    fn listener_of_missing_entry() {
+        let mut tracked: MapTracker<String, i32, String> = MapTracker::new();
+        let added = tracked.add_listener("a", "key".to_string(), |_: &i32, _: &i32| {});
+        assert!(added.err() == Some(NoEntry));
+
+        tracked.insert("a".to_string(), 1);
+        assert!(tracked.add_listener("a", "key".to_string(), |_: &i32, _: &i32| {}).is_ok());
+        assert!(tracked.remove_listener("a", "other").is_none());
+        assert!(tracked.remove_listener("a", "key").is_some());
+        assert!(tracked.remove_listener("b", "key").is_none());
+    }
+}
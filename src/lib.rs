@@ -17,9 +17,9 @@
 //! scoped guard with two key properties:
 //!
 //! - It implements the `DerefMut` trait to allow ergonomic access to the
-//! underlying data.
+//!   underlying data.
 //! - It implements the `Drop` trait which checks if the underlying
-//! data was changed and, if so, notifies the listeners.
+//!   data was changed and, if so, notifies the listeners.
 //!
 //! Futhermore, [`DataTracker::as_ref()`](./struct.DataTracker.html#method.as_ref)
 //! returns a (non-mutable) reference to the data for cases when only
@@ -48,11 +48,12 @@
 //! // Register a listener which is called when a data change is noticed.
 //! let key = 0; // Keep the key to remove the callback later.
 //!
-//! // At the time or writing, the rust compiler could not infer the type
-//! // of the closure and therefore I needed to annotate the argument types.
-//! tracked_data.add_listener(key, Box::new(|old_value: &MyData, new_value: &MyData| {
+//! // At the time or writing, the rust compiler could not infer the
+//! // argument types of the closure and therefore I needed to annotate
+//! // them. Use `add_fn()` to pass the closure without annotations.
+//! tracked_data.add_listener(key, |old_value: &MyData, new_value: &MyData| {
 //!     println!("changed {:?} -> {:?}", old_value, new_value);
-//! }));
+//! });
 //!
 //! {
 //!     // Create x, a (non-mutable) reference to original data.
@@ -72,308 +73,4494 @@
 //! // Remove our callback.
 //! tracked_data.remove_listener(&key);
 //! ```
+//!
+//! With the `no_std` feature, the crate depends only on `core` and `alloc`.
+//! Callbacks are then kept in a `BTreeMap`, so keys must implement `Ord`
+//! rather than `Hash + Eq` (see [`ListenerKey`](./trait.ListenerKey.html)).
+//! Whatever needs threads, locks, hashing or unwinding is not available:
+//! `SharedTracker`, `HashTracker`, `FieldTracker`, `changes()`,
+//! `add_sender()`, `add_debounced_listener()`, `add_offloaded_listener()` and
+//! `map()`. Panics of callbacks are not caught.
+//!
+//! With the `timer` feature, `add_debounced_listener()`,
+//! `add_rate_limited_listener()` and `add_windowed_listener()` deliver
+//! changes later from one timer thread shared by all trackers, so a slow
+//! callback delays the other timed listeners.
+
+#![cfg_attr(all(feature = "no_std", not(test)), no_std)]
+
+// Without std, the parts of std which are re-exported from core are still
+// reachable under their usual paths.
+#[cfg(all(feature = "no_std", not(test)))]
+extern crate core as std;
+extern crate alloc;
 
+#[cfg(feature = "derive")]
+extern crate data_tracker_derive;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+#[cfg(not(feature = "no_std"))]
 use std::collections::HashMap;
+#[cfg(feature = "no_std")]
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "no_std"))]
 use std::hash::Hash;
-use std::cmp::Eq;
+use std::any::Any;
+use std::borrow::Borrow;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(not(feature = "no_std"))]
+mod bounded;
+mod cell;
+#[cfg(not(feature = "no_std"))]
+mod changes;
+#[cfg(not(feature = "no_std"))]
+mod coalesce;
+mod context;
+mod diff;
+mod fold;
+mod guard;
+#[cfg(not(feature = "no_std"))]
+mod fields;
+#[cfg(not(feature = "no_std"))]
+mod hashed;
+mod history;
+mod keyed;
+#[cfg(not(any(feature = "no_send", feature = "no_std")))]
+mod offload;
+mod path;
+mod propagation;
+#[cfg(not(feature = "no_std"))]
+mod removal;
+#[cfg(not(feature = "no_std"))]
+mod shared;
+#[cfg(not(feature = "no_std"))]
+mod sink;
+mod snapshot;
+#[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+mod timed;
+mod transaction;
+mod typed;
+
+#[cfg(feature = "async")]
+pub use asynchronous::{AsyncOnChanged, BoxFuture, Commit};
+#[cfg(not(feature = "no_std"))]
+pub use bounded::{BoundedReceiver, DropPolicy};
+pub use cell::TrackerCell;
+#[cfg(not(feature = "no_std"))]
+pub use changes::{Changes, Next};
+#[cfg(not(feature = "no_std"))]
+pub use coalesce::Coalescer;
+pub use context::ContextModifier;
+pub use diff::{DiffModifier, DiffOnChanged, DiffTracker};
+#[cfg(not(feature = "no_std"))]
+pub use fields::{FieldModifier, FieldTracker, TrackFields};
+pub use guard::ReadGuard;
+#[cfg(not(feature = "no_std"))]
+pub use hashed::{HashModifier, HashTracker};
+pub use history::{HistoryModifier, HistoryTracker};
+pub use keyed::{MapTracker, NoEntry};
+#[cfg(not(any(feature = "no_send", feature = "no_std")))]
+pub use offload::{Executor, Job, WorkerPool};
+pub use path::{PathError, PathLookup};
+pub use propagation::{HandlingOnChanged, Propagation};
+#[cfg(not(feature = "no_std"))]
+pub use removal::ListenerRemover;
+#[cfg(not(feature = "no_std"))]
+pub use shared::{SharedTracker, Subscription, WouldBlock};
+#[cfg(not(feature = "no_std"))]
+pub use sink::{ChangeEvent, ChangeSink};
+pub use snapshot::{SnapshotModifier, SnapshotOnChanged, SnapshotTracker};
+pub use transaction::Transaction;
+pub use typed::TypeKeyedTracker;
+
+#[cfg(feature = "derive")]
+pub use data_tracker_derive::TrackFields;
+
+/// Marker trait for types which must be `Send` unless the `no_send` feature
+/// is enabled.
+#[cfg(not(feature = "no_send"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(feature = "no_send"))]
+impl<T: Send> MaybeSend for T {}
+
+#[cfg(feature = "no_send")]
+pub trait MaybeSend {}
+
+#[cfg(feature = "no_send")]
+impl<T> MaybeSend for T {}
+
+/// Marker trait for types which must be `Sync` unless the `no_send` feature
+/// is enabled.
+///
+/// Callbacks need not be `Sync`, since a tracker only calls them through
+/// `&mut self`. This is required of what a tracker shares between threads
+/// anyway, such as comparators, which clones of a tracker share.
+#[cfg(not(feature = "no_send"))]
+pub trait MaybeSync: Sync {}
+
+#[cfg(not(feature = "no_send"))]
+impl<T: Sync> MaybeSync for T {}
+
+#[cfg(feature = "no_send")]
+pub trait MaybeSync {}
+
+#[cfg(feature = "no_send")]
+impl<T> MaybeSync for T {}
+
+/// The requirements on the type of the keys under which callbacks are
+/// registered.
+///
+/// This is `Hash + Eq`, or `Ord` with the `no_std` feature, where the
+/// callbacks are stored in a `BTreeMap` rather than a `HashMap`.
+#[cfg(not(feature = "no_std"))]
+pub trait ListenerKey: Hash + Eq {}
+
+#[cfg(not(feature = "no_std"))]
+impl<K: ?Sized + Hash + Eq> ListenerKey for K {}
+
+#[cfg(feature = "no_std")]
+pub trait ListenerKey: Ord {}
+
+#[cfg(feature = "no_std")]
+impl<K: ?Sized + Ord> ListenerKey for K {}
+
+/// Trait deciding whether a value changed, used by `DataTracker::new()`
+/// to detect changes.
+///
+/// This is implemented for all types implementing `PartialEq`, where a value
+/// changed if it is not equal to the old value. Types without `PartialEq`, or
+/// for which equality is too strict, can implement it themselves, for
+/// example to ignore a field which does not matter to the listeners.
+pub trait ChangeDetect {
+    /// Return `true` if going from `old` to `new` is a change to report.
+    fn is_changed(old: &Self, new: &Self) -> bool;
+}
+
+impl<T: ?Sized + PartialEq> ChangeDetect for T {
+    fn is_changed(old: &T, new: &T) -> bool {
+        old != new
+    }
+}
+
+/// The map in which callbacks are handed to and returned by a tracker, for
+/// example by `DataTracker::set_listeners()`.
+///
+/// This is a `HashMap`, or a `BTreeMap` with the `no_std` feature.
+#[cfg(not(feature = "no_std"))]
+pub type Map<K, V> = HashMap<K, V>;
+
+/// The map in which callbacks are handed to and returned by a tracker, for
+/// example by `DataTracker::set_listeners()`.
+///
+/// This is a `HashMap`, or a `BTreeMap` with the `no_std` feature.
+#[cfg(feature = "no_std")]
+pub type Map<K, V> = BTreeMap<K, V>;
+
+/// Error reported by a fallible change notification callback.
+pub type ListenerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Keeps the dispatch methods of `OnChanged` private to this crate.
+mod sealed {
+    /// Passed to the hidden methods of `OnChanged`. It cannot be named
+    /// outside of this crate.
+    #[derive(Clone, Copy)]
+    pub struct Token;
+}
 
 /// Trait defining change notification callback function.
+///
+/// Unless the `no_send` feature is enabled, callbacks must be `Send`. They
+/// need not be `Sync`: the trackers only call them through `&mut self`, so a
+/// tracker is `Sync` either way.
+///
+/// Implementations provide only `on_changed()`. Callbacks which report
+/// errors, receive a context or stop the remaining callbacks are added with
+/// `add_try_listener()`, `add_context_listener()` and
+/// `add_handling_listener()` respectively.
 #[cfg(not(feature = "no_send"))]
 pub trait OnChanged<T>: Send {
-    fn on_changed(&self, old_value: &T, new_value: &T) -> ();
+    /// Called with the value from before the change and the current value.
+    ///
+    /// For a change made through a `Modifier`, `old_value` is the copy made
+    /// when the `Modifier` was created, whatever intermediate values the data
+    /// had within its scope.
+    fn on_changed(&self, old_value: &T, new_value: &T);
+
+    // The methods below are used by the listeners of this crate only. They
+    // take a `sealed::Token`, which cannot be named outside of this crate, so
+    // they can be neither called nor overridden by other implementations.
+
+    /// Like `on_changed()`, but allow reporting a failure.
+    #[doc(hidden)]
+    fn try_on_changed(&self,
+                      old_value: &T,
+                      new_value: &T,
+                      _: sealed::Token)
+                      -> Result<(), ListenerError> {
+        self.on_changed(old_value, new_value);
+        Ok(())
+    }
+
+    /// Like `try_on_changed()`, but also receive the context of the change,
+    /// if any, and tell whether the remaining callbacks are called.
+    #[doc(hidden)]
+    fn handle_in_context(&self,
+                         _context: Option<&dyn Any>,
+                         old_value: &T,
+                         new_value: &T,
+                         token: sealed::Token)
+                         -> Result<Propagation, ListenerError> {
+        self.try_on_changed(old_value, new_value, token).map(|()| Propagation::Continue)
+    }
+
+    /// Return `false` once this callback no longer needs to be called.
+    #[doc(hidden)]
+    fn is_alive(&self, _: sealed::Token) -> bool {
+        true
+    }
 }
 
 #[cfg(feature = "no_send")]
 pub trait OnChanged<T> {
-    fn on_changed(&self, old_value: &T, new_value: &T) -> ();
+    /// Called with the value from before the change and the current value.
+    ///
+    /// For a change made through a `Modifier`, `old_value` is the copy made
+    /// when the `Modifier` was created, whatever intermediate values the data
+    /// had within its scope.
+    fn on_changed(&self, old_value: &T, new_value: &T);
+
+    // The methods below are used by the listeners of this crate only. They
+    // take a `sealed::Token`, which cannot be named outside of this crate, so
+    // they can be neither called nor overridden by other implementations.
+
+    /// Like `on_changed()`, but allow reporting a failure.
+    #[doc(hidden)]
+    fn try_on_changed(&self,
+                      old_value: &T,
+                      new_value: &T,
+                      _: sealed::Token)
+                      -> Result<(), ListenerError> {
+        self.on_changed(old_value, new_value);
+        Ok(())
+    }
+
+    /// Like `try_on_changed()`, but also receive the context of the change,
+    /// if any, and tell whether the remaining callbacks are called.
+    #[doc(hidden)]
+    fn handle_in_context(&self,
+                         _context: Option<&dyn Any>,
+                         old_value: &T,
+                         new_value: &T,
+                         token: sealed::Token)
+                         -> Result<Propagation, ListenerError> {
+        self.try_on_changed(old_value, new_value, token).map(|()| Propagation::Continue)
+    }
+
+    /// Return `false` once this callback no longer needs to be called.
+    #[doc(hidden)]
+    fn is_alive(&self, _: sealed::Token) -> bool {
+        true
+    }
 }
 
 #[cfg(not(feature = "no_send"))]
 impl<F, T> OnChanged<T> for F
-    where F: Fn(&T, &T) -> () + Send
+    where F: Fn(&T, &T) + Send
 {
-    fn on_changed(&self, old_value: &T, new_value: &T) -> () {
+    fn on_changed(&self, old_value: &T, new_value: &T) {
         self(old_value, new_value)
     }
 }
 
 #[cfg(feature = "no_send")]
 impl<F, T> OnChanged<T> for F
-    where F: Fn(&T, &T) -> ()
+    where F: Fn(&T, &T)
 {
-    fn on_changed(&self, old_value: &T, new_value: &T) -> () {
+    fn on_changed(&self, old_value: &T, new_value: &T) {
         self(old_value, new_value)
     }
 }
 
-struct Inner<T, K>
-    where T: Clone + PartialEq,
-          K: Hash + Eq
-{
-    value: T,
-    fn_map: HashMap<K, Box<OnChanged<T>>>,
-}
+/// Lets a boxed callback be passed wherever a callback is expected.
+impl<T> OnChanged<T> for Box<dyn OnChanged<T>> {
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        (**self).on_changed(old_value, new_value)
+    }
 
-impl<T, K> Inner<T, K>
-    where T: Clone + PartialEq,
-          K: Hash + Eq
-{
-    fn add_listener(&mut self, key: K, f: Box<OnChanged<T>>) -> Option<Box<OnChanged<T>>> {
-        self.fn_map.insert(key, f)
+    fn try_on_changed(&self,
+                      old_value: &T,
+                      new_value: &T,
+                      token: sealed::Token)
+                      -> Result<(), ListenerError> {
+        (**self).try_on_changed(old_value, new_value, token)
     }
-    fn remove_listener(&mut self, key: &K) -> Option<Box<OnChanged<T>>> {
-        self.fn_map.remove(key)
+
+    fn handle_in_context(&self,
+                         context: Option<&dyn Any>,
+                         old_value: &T,
+                         new_value: &T,
+                         token: sealed::Token)
+                         -> Result<Propagation, ListenerError> {
+        (**self).handle_in_context(context, old_value, new_value, token)
     }
-    fn notify_listeners(&self, modifier: &Modifier<T, K>) {
-        let orig_value = &modifier.orig_copy;
-        let new_value: &T = modifier;
-        for on_changed_obj in self.fn_map.values() {
-            on_changed_obj.on_changed(orig_value, new_value);
-        }
+
+    fn is_alive(&self, token: sealed::Token) -> bool {
+        (**self).is_alive(token)
     }
 }
 
-/// Allow viewing and modifying data owned by `DataTracker`.
-///
-/// Create an instance of this by calling
-/// [`DataTracker::as_tracked_mut()`](./struct.DataTracker.html#method.as_tracked_mut).
-pub struct Modifier<'a, T, K>
-    where T: 'a + Clone + PartialEq,
-          K: 'a + Hash + Eq
+/// Trait defining fallible change notification callback function.
+#[cfg(not(feature = "no_send"))]
+pub trait TryOnChanged<T, E>: Send {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Result<(), E>;
+}
+
+#[cfg(feature = "no_send")]
+pub trait TryOnChanged<T, E> {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Result<(), E>;
+}
+
+#[cfg(not(feature = "no_send"))]
+impl<F, T, E> TryOnChanged<T, E> for F
+    where F: Fn(&T, &T) -> Result<(), E> + Send
 {
-    orig_copy: T,
-    inner_ref: &'a mut Inner<T, K>,
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Result<(), E> {
+        self(old_value, new_value)
+    }
 }
 
-impl<'a, T, K> Modifier<'a, T, K>
-    where T: 'a + Clone + PartialEq,
-          K: 'a + Hash + Eq
+#[cfg(feature = "no_send")]
+impl<F, T, E> TryOnChanged<T, E> for F
+    where F: Fn(&T, &T) -> Result<(), E>
 {
-    fn new(inner: &'a mut Inner<T, K>) -> Modifier<'a, T, K> {
-        let orig_copy: T = inner.value.clone();
-        Modifier {
-            orig_copy: orig_copy,
-            inner_ref: inner,
-        }
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Result<(), E> {
+        self(old_value, new_value)
     }
 }
 
-impl<'a, T, K> std::ops::Deref for Modifier<'a, T, K>
-    where T: 'a + Clone + PartialEq,
-          K: 'a + Hash + Eq
+/// Lets a boxed callback be passed wherever a callback is expected.
+impl<T, E> TryOnChanged<T, E> for Box<dyn TryOnChanged<T, E>> {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Result<(), E> {
+        (**self).on_changed(old_value, new_value)
+    }
+}
+
+/// Adapts a `TryOnChanged` callback so it can be stored with the others.
+struct Fallible<T, E> {
+    callback: Box<dyn TryOnChanged<T, E>>,
+}
+
+impl<T, E> OnChanged<T> for Fallible<T, E>
+    where E: Into<ListenerError>
 {
-    type Target = T;
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let _ = self.callback.on_changed(old_value, new_value);
+    }
 
-    fn deref(&self) -> &T {
-        &self.inner_ref.value
+    fn try_on_changed(&self,
+                      old_value: &T,
+                      new_value: &T,
+                      _: sealed::Token)
+                      -> Result<(), ListenerError> {
+        self.callback.on_changed(old_value, new_value).map_err(Into::into)
     }
 }
 
-impl<'a, T, K> std::ops::DerefMut for Modifier<'a, T, K>
-    where T: 'a + Clone + PartialEq,
-          K: 'a + Hash + Eq
+/// Only passes changes for which `predicate` returns `true` on to `callback`.
+struct Filtered<T, P> {
+    predicate: P,
+    callback: Box<dyn OnChanged<T>>,
+    /// The equality of the tracker, so that equal values are never passed
+    /// to `predicate`.
+    eq: Equality<T>,
+}
+
+impl<T, P> Filtered<T, P>
+    where P: Fn(&T, &T) -> bool
 {
-    fn deref_mut(&mut self) -> &mut T {
-        &mut self.inner_ref.value
+    fn accepts(&self, old_value: &T, new_value: &T) -> bool {
+        !self.eq.equal(old_value, new_value) && (self.predicate)(old_value, new_value)
     }
 }
 
-impl<'a, T, K> Drop for Modifier<'a, T, K>
-    where T: 'a + Clone + PartialEq,
-          K: 'a + Hash + Eq
+impl<T, P> OnChanged<T> for Filtered<T, P>
+    where P: Fn(&T, &T) -> bool + MaybeSend
 {
-    fn drop(&mut self) {
-        if self.orig_copy != self.inner_ref.value {
-            self.inner_ref.notify_listeners(self);
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        if self.accepts(old_value, new_value) {
+            self.callback.on_changed(old_value, new_value);
+        }
+    }
+
+    fn try_on_changed(&self,
+                      old_value: &T,
+                      new_value: &T,
+                      token: sealed::Token)
+                      -> Result<(), ListenerError> {
+        if self.accepts(old_value, new_value) {
+            self.callback.try_on_changed(old_value, new_value, token)
+        } else {
+            Ok(())
         }
     }
+
+    fn is_alive(&self, token: sealed::Token) -> bool {
+        self.callback.is_alive(token)
+    }
 }
 
-/// Tracks changes to data and notifies listeners.
-///
-/// The data to be tracked is type `T`.
-///
-/// Callbacks are stored in a `HashMap` with keys of type `K`.
-///
-/// See the [module-level documentation](./) for more details.
-pub struct DataTracker<T, K>
-    where T: Clone + PartialEq,
-          K: Hash + Eq
-{
-    inner: Inner<T, K>,
+/// Calls `callback` with the target of a `Weak` reference, as long as it
+/// exists.
+struct WeakListener<S, F> {
+    target: Weak<S>,
+    callback: F,
 }
 
-impl<T, K> DataTracker<T, K>
-    where T: Clone + PartialEq,
-          K: Hash + Eq
+impl<T, S, F> OnChanged<T> for WeakListener<S, F>
+    where F: Fn(&S, &T, &T) + MaybeSend,
+          Weak<S>: MaybeSend
 {
-    /// Create a new `DataTracker` which takes ownership
-    /// of the data of type `T`.
-    ///
-    /// Callbacks are registered via a key of type `K`.
-    pub fn new(value: T) -> DataTracker<T, K> {
-        DataTracker {
-            inner: Inner {
-                value: value,
-                fn_map: HashMap::new(),
-            },
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        if let Some(target) = self.target.upgrade() {
+            (self.callback)(&target, old_value, new_value);
         }
     }
 
-    /// Add a callback that will be called just after a data change is detected.
-    ///
-    /// If a previous callback exists with the `key`, the original callback is
-    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
-    pub fn add_listener(&mut self,
-                        key: K,
-                        callback: Box<OnChanged<T>>)
-                        -> Option<Box<OnChanged<T>>> {
-        self.inner.add_listener(key, callback)
+    fn is_alive(&self, _: sealed::Token) -> bool {
+        self.target.strong_count() > 0
     }
+}
 
-    /// Remove callback.
-    ///
-    /// If a callback exists with the `key`, it is removed and returned as
-    /// `Some(callback)`. Otherwise, `None` is returned.
-    pub fn remove_listener(&mut self, key: &K) -> Option<Box<OnChanged<T>>> {
-        self.inner.remove_listener(key)
+/// The outcome of
+/// [`DataTracker::replace_listener()`](./struct.DataTracker.html#method.replace_listener).
+pub enum ListenerSlot<T> {
+    /// No callback was registered with the key.
+    Inserted,
+    /// The key was in use and this is the callback which was displaced.
+    Replaced(Box<dyn OnChanged<T>>),
+}
+
+/// A callback together with its registration with a tracker: its
+/// priority, whether it is called only once, its group and whether it is
+/// enabled.
+///
+/// Callbacks are handed back in this form by
+/// [`DataTracker::drain_listeners()`](./struct.DataTracker.html#method.drain_listeners)
+/// and similar methods, so that they can be moved to another tracker without
+/// losing their registration. A boxed callback converts into a
+/// `Registration` as if added by `add_listener()`.
+pub struct Registration<T> {
+    listener: Listener<dyn OnChanged<T>>,
+}
+
+impl<T> Registration<T> {
+    /// Return the priority of the callback.
+    pub fn priority(&self) -> i32 {
+        self.listener.priority
     }
 
-    /// Return a `Modifier` which can be used to modify the owned data.
-    pub fn as_tracked_mut(&mut self) -> Modifier<T, K> {
-        Modifier::new(&mut self.inner)
+    /// Return `true` if the callback is removed after its next call.
+    pub fn is_once(&self) -> bool {
+        self.listener.once
     }
-}
 
-impl<T, K> AsRef<T> for DataTracker<T, K>
-    where T: Clone + PartialEq,
-          K: Hash + Eq
-{
-    fn as_ref(&self) -> &T {
-        &self.inner.value
+    /// Return the group of the callback, if any.
+    pub fn group(&self) -> Option<u64> {
+        self.listener.group
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::sync::{Arc, Mutex};
-    use super::DataTracker;
+    /// Return `false` if the callback is disabled.
+    pub fn is_enabled(&self) -> bool {
+        self.listener.enabled
+    }
 
-    #[test]
-    fn track_struct() {
-        #[derive(Clone, PartialEq)]
-        struct MyData {
-            a: u8,
-        }
+    /// Return the callback, discarding its registration.
+    pub fn into_callback(self) -> Box<dyn OnChanged<T>> {
+        self.listener.callback.into_inner()
+    }
+}
 
-        let change_count = Arc::new(Mutex::new(0));
-        let mut tracked_data = DataTracker::new(MyData { a: 1 });
+impl<T> From<Box<dyn OnChanged<T>>> for Registration<T> {
+    fn from(callback: Box<dyn OnChanged<T>>) -> Registration<T> {
+        let listener = Listener {
+            callback: Exclusive(callback),
+            priority: 0,
+            seq: 0,
+            once: false,
+            expired: false,
+            fires: 0,
+            enabled: true,
+            group: None,
+        };
+        Registration { listener }
+    }
+}
 
-        let cc2 = change_count.clone();
-        let key = 0;
-        tracked_data.add_listener(key,
-                                  Box::new(move |_: &MyData, _: &MyData| {
-                                      let ref mut data = *cc2.lock().unwrap();
-                                      *data = *data + 1;
-                                  }));
+/// The payload of a panic caught from a callback.
+type PanicPayload = Box<dyn Any + Send>;
 
-        assert!(*change_count.lock().unwrap() == 0);
+/// Call `f`, catching a panic.
+#[cfg(not(feature = "no_std"))]
+fn catch_panic<R, F: FnOnce() -> R>(f: F) -> Result<R, PanicPayload> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+}
 
-        {
-            let _x = tracked_data.as_ref();
-        }
-        assert!(*change_count.lock().unwrap() == 0);
+/// Call `f`. Without std, panics cannot be caught.
+#[cfg(feature = "no_std")]
+fn catch_panic<R, F: FnOnce() -> R>(f: F) -> Result<R, PanicPayload> {
+    Ok(f())
+}
 
-        {
-            let mut x = tracked_data.as_tracked_mut();
-            x.a = 10;
+/// Continue unwinding with a panic caught from a callback.
+///
+/// If the thread is already panicking, as when a `Modifier` is dropped during
+/// unwinding, the payload is dropped instead, since a second panic would abort
+/// the process.
+#[cfg(not(feature = "no_std"))]
+fn resume_panic(panic: Option<PanicPayload>) {
+    if let Some(payload) = panic {
+        if !std::thread::panicking() {
+            std::panic::resume_unwind(payload);
         }
-        assert!(*change_count.lock().unwrap() == 1);
+    }
+}
 
-        {
-            let mut x = tracked_data.as_tracked_mut();
-            x.a += 10;
-        }
-        assert!(*change_count.lock().unwrap() == 2);
+#[cfg(feature = "no_std")]
+fn resume_panic(_panic: Option<PanicPayload>) {}
 
-        assert!(tracked_data.as_ref().a == 20);
-        assert!(*change_count.lock().unwrap() == 2);
+/// Error reported by
+/// [`Modifier::try_commit()`](./struct.Modifier.html#method.try_commit) in
+/// place of a panic of a callback.
+#[derive(Debug)]
+pub struct ListenerPanic {
+    message: String,
+}
 
-        tracked_data.remove_listener(&key);
+impl ListenerPanic {
+    fn new(payload: &PanicPayload) -> ListenerPanic {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            (*message).to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        ListenerPanic { message }
     }
 
-    #[test]
-    fn track_enum() {
+    /// Return the message the callback panicked with.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
 
-        #[derive(Clone, PartialEq)]
-        enum MyEnum {
-            FirstValue,
-            SecondValue,
-        }
+impl std::fmt::Display for ListenerPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "listener panicked: {}", self.message)
+    }
+}
 
-        let change_count = Arc::new(Mutex::new(0));
-        let mut tracked_data = DataTracker::new(MyEnum::FirstValue);
+impl std::error::Error for ListenerPanic {}
 
-        let cc2 = change_count.clone();
-        let key = 0;
-        tracked_data.add_listener(key,
-                                  Box::new(move |_: &MyEnum, _: &MyEnum| {
-                                      let ref mut data = *cc2.lock().unwrap();
-                                      *data = *data + 1;
-                                  }));
+/// Error returned by
+/// [`DataTracker::add_listener_strict()`](./struct.DataTracker.html#method.add_listener_strict)
+/// when a callback is already registered with the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyExists<K> {
+    key: K,
+}
 
-        assert!(*change_count.lock().unwrap() == 0);
+impl<K> KeyExists<K> {
+    /// Return the key which is already in use.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
 
-        {
-            let _x = tracked_data.as_ref();
-        }
-        assert!(*change_count.lock().unwrap() == 0);
+    /// Return the key which is already in use, consuming the error.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
 
-        {
-            let mut x = tracked_data.as_tracked_mut();
-            *x = MyEnum::SecondValue;
-        }
-        assert!(*change_count.lock().unwrap() == 1);
+impl<K> std::fmt::Display for KeyExists<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a listener is already registered with this key")
+    }
+}
 
-        assert!(tracked_data.as_ref() == &MyEnum::SecondValue);
-        assert!(*change_count.lock().unwrap() == 1);
+impl<K: std::fmt::Debug> std::error::Error for KeyExists<K> {}
 
-        tracked_data.remove_listener(&key);
+/// Error returned by
+/// [`DataTracker::compare_and_set()`](./struct.DataTracker.html#method.compare_and_set)
+/// when the version differs from the expected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CasError {
+    actual: u64,
+}
+
+impl CasError {
+    /// Return the version the tracker had at the time of the call.
+    pub fn actual_version(&self) -> u64 {
+        self.actual
     }
+}
 
-    #[test]
-    fn callback_arg_order() {
+impl std::fmt::Display for CasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the tracker is at version {}", self.actual)
+    }
+}
 
-        #[derive(Clone, PartialEq)]
-        enum MyEnum {
-            FirstValue,
-            SecondValue,
-        }
+impl std::error::Error for CasError {}
 
-        let mut tracked_data = DataTracker::new(MyEnum::FirstValue);
+/// Gives access to a value only through `&mut self`.
+///
+/// Since a shared reference gives no access to the value, it can be shared
+/// between threads whenever it can be sent between them. The trackers keep
+/// their callbacks in this, so that a tracker is `Sync` even though the
+/// callbacks need only be `Send`.
+struct Exclusive<C>(C);
 
-        let did_run = Arc::new(Mutex::new(false));
-        let did_run_clone = did_run.clone();
+unsafe impl<C: Send> Sync for Exclusive<C> {}
 
-        tracked_data.add_listener(0,
-                                  Box::new(move |old_value: &MyEnum, new_value: &MyEnum| {
-                                      assert!(old_value == &MyEnum::FirstValue);
-                                      assert!(new_value == &MyEnum::SecondValue);
+impl<C> Exclusive<C> {
+    fn get_mut(&mut self) -> &mut C {
+        &mut self.0
+    }
+    fn into_inner(self) -> C {
+        self.0
+    }
+}
+
+/// A registered callback and the data needed to order its invocation.
+struct Listener<C: ?Sized> {
+    callback: Exclusive<Box<C>>,
+    priority: i32,
+    seq: u64,
+    once: bool,
+    expired: bool,
+    /// The number of times the callback was called, if counted.
+    fires: u64,
+    /// `false` while the callback is skipped, see `disable_listener()`.
+    enabled: bool,
+    /// The group the callback was added to, see `add_listener_in_group()`.
+    group: Option<u64>,
+}
+
+/// The registered callbacks of a tracker, where `C` is the callback trait
+/// object type.
+struct Listeners<C: ?Sized, K>
+    where K: ListenerKey
+{
+    fn_map: Map<K, Listener<C>>,
+    next_seq: u64,
+    count_fires: bool,
+    /// Keys queued for removal, see `DataTracker::listener_remover()`.
+    #[cfg(not(feature = "no_std"))]
+    removals: Option<removal::RemovalQueue<K>>,
+}
+
+impl<C: ?Sized, K> Listeners<C, K>
+    where K: ListenerKey
+{
+    fn new() -> Listeners<C, K> {
+        Listeners {
+            fn_map: Map::new(),
+            next_seq: 0,
+            count_fires: false,
+            #[cfg(not(feature = "no_std"))]
+            removals: None,
+        }
+    }
+    fn insert(&mut self, key: K, priority: i32, once: bool, f: Box<C>) -> Option<Box<C>> {
+        let listener = self.new_listener(priority, once, f);
+        self.fn_map.insert(key, listener).map(|l| l.callback.into_inner())
+    }
+    /// Like `insert()`, but keep an existing callback of `key` and return
+    /// `false` instead.
+    fn insert_if_absent(&mut self, key: K, priority: i32, once: bool, f: Box<C>) -> bool {
+        let mut inserted = false;
+        let listener = self.new_listener(priority, once, f);
+        self.fn_map.entry(key).or_insert_with(|| {
+            inserted = true;
+            listener
+        });
+        inserted
+    }
+    /// Insert a listener taken from another `Listeners`, keeping everything
+    /// but its place in the order, which is after all current listeners of
+    /// the same priority.
+    fn insert_listener(&mut self, key: K, mut listener: Listener<C>) -> Option<Listener<C>> {
+        self.apply_removals();
+        listener.seq = self.next_seq;
+        self.next_seq += 1;
+        self.fn_map.insert(key, listener)
+    }
+    fn new_listener(&mut self, priority: i32, once: bool, f: Box<C>) -> Listener<C> {
+        self.apply_removals();
+        let listener = Listener {
+            callback: Exclusive(f),
+            priority,
+            seq: self.next_seq,
+            once,
+            expired: false,
+            fires: 0,
+            enabled: true,
+            group: None,
+        };
+        self.next_seq += 1;
+        listener
+    }
+    fn remove<Q>(&mut self, key: &Q) -> Option<Box<C>>
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.apply_removals();
+        self.fn_map.remove(key).map(|l| l.callback.into_inner())
+    }
+    /// Return the listener of `key`, unless its removal is pending.
+    fn get<Q>(&self, key: &Q) -> Option<&Listener<C>>
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        if self.is_removal_pending(key) {
+            return None;
+        }
+        self.fn_map.get(key)
+    }
+    fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut Listener<C>>
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.apply_removals();
+        self.fn_map.get_mut(key)
+    }
+    fn contains<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.get(key).is_some()
+    }
+    /// Return the number of listeners whose removal is not pending.
+    fn len(&self) -> usize {
+        self.fn_map.keys().filter(|key| !self.is_removal_pending(*key)).count()
+    }
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Remove all listeners and return them, keeping the configuration.
+    fn take(&mut self) -> Map<K, Listener<C>> {
+        self.apply_removals();
+        std::mem::take(&mut self.fn_map)
+    }
+    /// Re-key the listeners with `f`. On collisions, the listener called
+    /// last wins.
+    fn map_key<K2, F>(mut self, f: F) -> Listeners<C, K2>
+        where K2: ListenerKey,
+              F: Fn(K) -> K2
+    {
+        self.apply_removals();
+        let mut listeners: Vec<(K, Listener<C>)> = self.fn_map.into_iter().collect();
+        listeners.sort_by_key(|(_, l)| (l.priority, l.seq));
+        Listeners {
+            fn_map: listeners.into_iter().map(|(key, l)| (f(key), l)).collect(),
+            next_seq: self.next_seq,
+            count_fires: self.count_fires,
+            // Queued keys cannot be re-keyed, so existing removers are detached.
+            #[cfg(not(feature = "no_std"))]
+            removals: None,
+        }
+    }
+    /// Return the listeners in the order in which they are called.
+    fn ordered(&self) -> Vec<(&K, &Listener<C>)> {
+        let mut listeners: Vec<(&K, &Listener<C>)> =
+            self.fn_map.iter().filter(|&(key, _)| !self.is_removal_pending(key)).collect();
+        listeners.sort_by_key(|&(_, l)| (l.priority, l.seq));
+        listeners
+    }
+    /// Pass each callback to `call`, in order, and drop those which should
+    /// not be called again.
+    ///
+    /// A panic in `call` does not prevent the remaining callbacks from being
+    /// called. The first panic is returned so the caller can resume it.
+    fn dispatch_catching<F>(&mut self, mut call: F) -> (Vec<ListenerError>, Option<PanicPayload>)
+        where F: FnMut(&C) -> Result<(), ListenerError>
+    {
+        self.dispatch_propagating(|callback| call(callback).map(|()| Propagation::Continue))
+    }
+    /// Like `dispatch_catching()`, but stop calling callbacks once `call`
+    /// returns `Propagation::Stop`.
+    fn dispatch_propagating<F>(&mut self, mut call: F) -> (Vec<ListenerError>, Option<PanicPayload>)
+        where F: FnMut(&C) -> Result<Propagation, ListenerError>
+    {
+        self.apply_removals();
+        let count_fires = self.count_fires;
+        let mut listeners: Vec<&mut Listener<C>> = self.fn_map.values_mut().collect();
+        listeners.sort_by_key(|l| (l.priority, l.seq));
+        let mut errors = Vec::new();
+        let mut panic = None;
+        for listener in listeners {
+            if !listener.enabled {
+                continue;
+            }
+            if count_fires {
+                listener.fires += 1;
+            }
+            let mut propagation = Propagation::Continue;
+            match catch_panic(|| call(listener.callback.get_mut())) {
+                Ok(Ok(p)) => propagation = p,
+                Ok(Err(e)) => errors.push(e),
+                Err(payload) => {
+                    if panic.is_none() {
+                        panic = Some(payload);
+                    }
+                }
+            }
+            if listener.once {
+                listener.expired = true;
+            }
+            if propagation == Propagation::Stop {
+                break;
+            }
+        }
+        self.fn_map.retain(|_, l| !l.expired);
+        // Removals requested by the callbacks take effect after the dispatch.
+        self.apply_removals();
+        (errors, panic)
+    }
+    /// Like `dispatch_catching()`, but resume a panic once all callbacks
+    /// have been called.
+    fn dispatch<F>(&mut self, call: F) -> Vec<ListenerError>
+        where F: FnMut(&C) -> Result<(), ListenerError>
+    {
+        let (errors, panic) = self.dispatch_catching(call);
+        resume_panic(panic);
+        errors
+    }
+}
+
+#[cfg(feature = "no_std")]
+impl<C: ?Sized, K> Listeners<C, K>
+    where K: ListenerKey
+{
+    fn apply_removals(&mut self) {}
+    fn is_removal_pending<Q>(&self, _key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        false
+    }
+}
+
+impl<T, K> Listeners<dyn OnChanged<T>, K>
+    where K: ListenerKey
+{
+    fn notify_catching(&mut self,
+                       old_value: &T,
+                       new_value: &T)
+                       -> (Vec<ListenerError>, Option<PanicPayload>) {
+        self.notify_catching_in_context(None, old_value, new_value)
+    }
+    fn notify_catching_in_context(&mut self,
+                                  context: Option<&dyn Any>,
+                                  old_value: &T,
+                                  new_value: &T)
+                                  -> (Vec<ListenerError>, Option<PanicPayload>) {
+        self.fn_map.retain(|_, l| l.callback.get_mut().is_alive(sealed::Token));
+        self.dispatch_propagating(|callback| {
+            callback.handle_in_context(context, old_value, new_value, sealed::Token)
+        })
+    }
+}
+
+/// A bounded record of the most recent `(old, new)` value pairs.
+#[derive(Clone)]
+struct Journal<T> {
+    // Up to twice `capacity` entries are stored, so that dropping old entries
+    // in bulk keeps the most recent ones contiguous at little cost.
+    entries: Vec<(T, T)>,
+    capacity: usize,
+}
+
+impl<T> Journal<T> {
+    fn new(capacity: usize) -> Journal<T> {
+        Journal {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+    fn push(&mut self, old_value: T, new_value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == 2 * self.capacity {
+            self.entries.drain(..self.capacity);
+        }
+        self.entries.push((old_value, new_value));
+    }
+    fn recent(&self) -> &[(T, T)] {
+        let start = self.entries.len().saturating_sub(self.capacity);
+        &self.entries[start..]
+    }
+}
+
+#[cfg(not(feature = "no_send"))]
+type ComparatorFn<T> = Arc<dyn Fn(&T, &T) -> bool + Send + Sync>;
+
+#[cfg(feature = "no_send")]
+type ComparatorFn<T> = Arc<dyn Fn(&T, &T) -> bool>;
+
+#[cfg(not(feature = "no_send"))]
+type NormalizerFn<T> = Arc<dyn Fn(&mut T) + Send + Sync>;
+
+#[cfg(feature = "no_send")]
+type NormalizerFn<T> = Arc<dyn Fn(&mut T)>;
+
+/// How a tracker decides whether two values are equal.
+#[derive(Clone)]
+enum Equality<T> {
+    Fn(fn(&T, &T) -> bool),
+    Comparator(ComparatorFn<T>),
+}
+
+impl<T> Equality<T> {
+    fn equal(&self, a: &T, b: &T) -> bool {
+        match *self {
+            Equality::Fn(eq) => eq(a, b),
+            Equality::Comparator(ref eq) => eq(a, b),
+        }
+    }
+}
+
+struct Inner<T, K>
+    where T: Clone,
+          K: ListenerKey
+{
+    value: T,
+    listeners: Listeners<dyn OnChanged<T>, K>,
+    #[cfg(feature = "async")]
+    async_listeners: Listeners<dyn asynchronous::AsyncOnChanged<T>, K>,
+    journal: Option<Journal<T>>,
+    #[cfg(not(feature = "no_std"))]
+    sink: Option<Exclusive<Box<dyn sink::ChangeSink<T>>>>,
+    /// Called after the keyed listeners, see `set_default_listener()`.
+    default_listener: Option<Exclusive<Box<dyn OnChanged<T>>>>,
+    /// Accumulators updated with each change, see `add_fold_listener()`.
+    folds: Map<K, Box<dyn fold::Fold<T>>>,
+    /// Returns `true` if two values are considered equal, so that changing
+    /// one into the other is not reported.
+    eq: Equality<T>,
+    /// Adjusts the data after each modification, before change detection.
+    normalizer: Option<NormalizerFn<T>>,
+    /// The number of changes notified so far.
+    version: u64,
+    /// The number of `Modifier` scopes which ended with and without a
+    /// change.
+    change_count: u64,
+    noop_count: u64,
+    enabled: bool,
+    /// The value when notifications were disabled, if it was observed.
+    catch_up: Option<T>,
+    /// The number of outstanding `ReadGuard`s.
+    #[cfg(feature = "debug_checks")]
+    readers: std::sync::atomic::AtomicUsize,
+}
+
+impl<T, K> Inner<T, K>
+    where T: Clone,
+          K: ListenerKey
+{
+    fn new(value: T) -> Inner<T, K>
+        where T: ChangeDetect
+    {
+        fn unchanged<T: ChangeDetect>(old_value: &T, new_value: &T) -> bool {
+            !T::is_changed(old_value, new_value)
+        }
+        Inner::with_eq(value, Equality::Fn(unchanged))
+    }
+    fn with_eq(value: T, eq: Equality<T>) -> Inner<T, K> {
+        Inner {
+            value,
+            listeners: Listeners::new(),
+            #[cfg(feature = "async")]
+            async_listeners: Listeners::new(),
+            journal: None,
+            #[cfg(not(feature = "no_std"))]
+            sink: None,
+            default_listener: None,
+            folds: Map::new(),
+            eq,
+            normalizer: None,
+            version: 0,
+            change_count: 0,
+            noop_count: 0,
+            enabled: true,
+            catch_up: None,
+            #[cfg(feature = "debug_checks")]
+            readers: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+    /// Add the listeners of `registrations`, in the order of their
+    /// previous registration.
+    fn add_registrations<L>(&mut self, registrations: Map<K, L>)
+        where L: Into<Registration<T>>
+    {
+        let mut listeners: Vec<(K, Listener<dyn OnChanged<T>>)> = registrations.into_iter()
+            .map(|(key, registration)| (key, registration.into().listener))
+            .collect();
+        listeners.sort_by_key(|(_, l)| (l.priority, l.seq));
+        for (key, listener) in listeners {
+            self.listeners.insert_listener(key, listener);
+        }
+    }
+    /// Re-key the listeners and accumulators with `f`.
+    fn map_key<K2, F>(self, f: F) -> Inner<T, K2>
+        where K2: ListenerKey,
+              F: Fn(K) -> K2
+    {
+        Inner {
+            value: self.value,
+            listeners: self.listeners.map_key(&f),
+            #[cfg(feature = "async")]
+            async_listeners: self.async_listeners.map_key(&f),
+            journal: self.journal,
+            #[cfg(not(feature = "no_std"))]
+            sink: self.sink,
+            default_listener: self.default_listener,
+            folds: self.folds.into_iter().map(|(key, fold)| (f(key), fold)).collect(),
+            eq: self.eq,
+            normalizer: self.normalizer,
+            version: self.version,
+            change_count: self.change_count,
+            noop_count: self.noop_count,
+            enabled: self.enabled,
+            catch_up: self.catch_up,
+            #[cfg(feature = "debug_checks")]
+            readers: self.readers,
+        }
+    }
+    /// Return a copy of the data and the configuration, without any
+    /// listeners, accumulators or sink.
+    fn clone_without_listeners(&self) -> Inner<T, K> {
+        let mut inner = Inner::with_eq(self.value.clone(), self.eq.clone());
+        inner.listeners.count_fires = self.listeners.count_fires;
+        inner.journal = self.journal.clone();
+        inner.normalizer = self.normalizer.clone();
+        inner.version = self.version;
+        inner.enabled = self.enabled;
+        inner.catch_up = self.catch_up.clone();
+        inner
+    }
+    /// Return `true` if a change needs to be detected, because it would be
+    /// reported to a listener or recorded in the journal.
+    fn is_observed(&self) -> bool {
+        self.enabled &&
+        (!self.listeners.is_empty() || self.default_listener.is_some() ||
+         self.journal.is_some() || !self.folds.is_empty() || self.has_async_listeners() ||
+         self.has_sink())
+    }
+    /// Replace the value and notify the listeners if it changed. Returns
+    /// the previous value and whether it changed.
+    fn replace(&mut self, value: T) -> (T, bool) {
+        self.check_readers();
+        let old_value = std::mem::replace(&mut self.value, value);
+        self.normalize();
+        let changed = self.changed_from(&old_value);
+        if changed {
+            self.notify_listeners(&old_value);
+        }
+        (old_value, changed)
+    }
+    /// Apply the normalizer, if any, to the data.
+    fn normalize(&mut self) {
+        if let Some(ref normalizer) = self.normalizer {
+            normalizer(&mut self.value);
+        }
+    }
+    /// Return `true` if the data is to be reported as changed from
+    /// `old_value`.
+    fn changed_from(&self, old_value: &T) -> bool {
+        !self.eq.equal(old_value, &self.value)
+    }
+    #[cfg(not(feature = "no_std"))]
+    fn has_sink(&self) -> bool {
+        self.sink.is_some()
+    }
+    #[cfg(feature = "no_std")]
+    fn has_sink(&self) -> bool {
+        false
+    }
+    #[cfg(feature = "async")]
+    fn has_async_listeners(&self) -> bool {
+        !self.async_listeners.fn_map.is_empty()
+    }
+    #[cfg(not(feature = "async"))]
+    fn has_async_listeners(&self) -> bool {
+        false
+    }
+    /// Record a detected change from `old_value` in the journal and the
+    /// accumulators and pass it to the sink.
+    fn record(&mut self, old_value: &T) {
+        if let Some(ref mut journal) = self.journal {
+            journal.push(old_value.clone(), self.value.clone());
+        }
+        for fold in self.folds.values_mut() {
+            fold.update(old_value, &self.value);
+        }
+        #[cfg(not(feature = "no_std"))]
+        self.notify_sink(old_value);
+    }
+    /// Record a detected change from `old_value` and notify the listeners.
+    fn notify_listeners(&mut self, old_value: &T) -> Vec<ListenerError> {
+        self.notify_listeners_in_context(None, old_value)
+    }
+    /// Like `notify_listeners()`, but pass `context` to the callbacks.
+    fn notify_listeners_in_context(&mut self,
+                                   context: Option<&dyn Any>,
+                                   old_value: &T)
+                                   -> Vec<ListenerError> {
+        self.version += 1;
+        if !self.enabled {
+            return Vec::new();
+        }
+        self.record(old_value);
+        let (errors, panic) = self.call_listeners(context, Some(old_value));
+        resume_panic(panic);
+        errors
+    }
+    /// Like `notify_listeners()`, but report a panic of a callback as a
+    /// `ListenerPanic` error rather than resuming it.
+    fn try_notify_listeners(&mut self,
+                            context: Option<&dyn Any>,
+                            old_value: &T)
+                            -> Vec<ListenerError> {
+        self.version += 1;
+        if !self.enabled {
+            return Vec::new();
+        }
+        self.record(old_value);
+        let (mut errors, panic) = self.call_listeners(context, Some(old_value));
+        if let Some(payload) = panic {
+            errors.push(Box::new(ListenerPanic::new(&payload)));
+        }
+        errors
+    }
+    /// Call the keyed listeners and then the default listener, catching
+    /// panics. An `old_value` of `None` passes the current value as both
+    /// values.
+    fn call_listeners(&mut self,
+                      context: Option<&dyn Any>,
+                      old_value: Option<&T>)
+                      -> (Vec<ListenerError>, Option<PanicPayload>) {
+        let new_value = &self.value;
+        let old_value = old_value.unwrap_or(new_value);
+        let (mut errors, mut panic) =
+            self.listeners.notify_catching_in_context(context, old_value, new_value);
+        if let Some(ref mut callback) = self.default_listener {
+            let callback = callback.get_mut();
+            let handle = || callback.handle_in_context(context, old_value, new_value, sealed::Token);
+            match catch_panic(handle) {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => errors.push(e),
+                Err(payload) => panic = panic.or(Some(payload)),
+            }
+        }
+        (errors, panic)
+    }
+}
+
+/// Allow viewing and modifying data owned by `DataTracker`.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::as_tracked_mut()`](./struct.DataTracker.html#method.as_tracked_mut).
+pub struct Modifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    orig_copy: Option<T>,
+    inner_ref: &'a mut Inner<T, K>,
+    /// Set by `mark_dirty()` or `mark_clean()` to replace the comparison.
+    dirty: Option<bool>,
+    /// `true` once the modifications were committed, or for a nested scope.
+    finished: bool,
+}
+
+impl<'a, T, K> Modifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    fn new(inner: &'a mut Inner<T, K>) -> Modifier<'a, T, K> {
+        let mut modifier = Modifier::new_without_copy(inner);
+        // If nobody can observe a change, skip the copy.
+        if modifier.inner_ref.is_observed() {
+            modifier.orig_copy = Some(modifier.inner_ref.value.clone());
+        }
+        modifier
+    }
+    fn new_without_copy(inner: &'a mut Inner<T, K>) -> Modifier<'a, T, K> {
+        inner.check_readers();
+        Modifier {
+            orig_copy: None,
+            inner_ref: inner,
+            dirty: None,
+            finished: false,
+        }
+    }
+
+    /// Check for a change and notify the listeners, at most once.
+    ///
+    /// If `catch_panics` is `true`, panics of callbacks are reported as
+    /// errors. Otherwise, the first one is resumed after all callbacks ran.
+    ///
+    /// The `context` is passed to the callbacks, see `ContextModifier`.
+    fn finish(&mut self,
+              context: Option<&dyn Any>,
+              catch_panics: bool)
+              -> (bool, Vec<ListenerError>) {
+        match self.settle() {
+            Some(ref orig_copy) => {
+                let errors = if catch_panics {
+                    self.inner_ref.try_notify_listeners(context, orig_copy)
+                } else {
+                    self.inner_ref.notify_listeners_in_context(context, orig_copy)
+                };
+                (true, errors)
+            }
+            None => (false, Vec::new()),
+        }
+    }
+
+    /// End this `Modifier` and update the counters and the version, at most
+    /// once.
+    ///
+    /// Returns the original copy if the data changed, in which case the
+    /// caller must notify the listeners.
+    fn settle(&mut self) -> Option<T> {
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        self.inner_ref.normalize();
+        match self.orig_copy.take() {
+            Some(orig_copy) => {
+                if self.is_changed_from(&orig_copy) {
+                    self.inner_ref.change_count += 1;
+                    Some(orig_copy)
+                } else {
+                    self.inner_ref.noop_count += 1;
+                    None
+                }
+            }
+            None => {
+                // Without a copy a change cannot be ruled out, so count one.
+                if self.dirty != Some(false) {
+                    self.inner_ref.version += 1;
+                }
+                None
+            }
+        }
+    }
+
+    /// Return a `Modifier` for a nested scope of modifications.
+    ///
+    /// The nested `Modifier` takes no copy and performs no check when it is
+    /// dropped or committed, so its `commit()` and `changed()` return
+    /// `false`. The net change across all nested scopes is detected once,
+    /// when this (outer) `Modifier` is dropped, like with
+    /// `DataTracker::batch()`. This allows passing a `Modifier` to code which
+    /// expects to own one without causing extra notifications.
+    pub fn nested(&mut self) -> Modifier<'_, T, K> {
+        Modifier {
+            orig_copy: None,
+            inner_ref: &mut *self.inner_ref,
+            dirty: None,
+            finished: true,
+        }
+    }
+
+    /// Check for a change and notify the listeners now, rather than on drop.
+    ///
+    /// Returns `true` if the data changed and the listeners were called.
+    /// Consuming the `Modifier` means the
+    /// listeners are called exactly once, at this point. When `commit()` is
+    /// not called, the same check is performed when the `Modifier` is dropped.
+    pub fn commit(mut self) -> bool {
+        self.finish(None, false).0
+    }
+
+    /// Like `commit()`, but also report errors from fallible listeners.
+    ///
+    /// Returns `Ok(changed)` if no listener reported an error, where `changed`
+    /// is `true` if the data changed. Otherwise, every listener is still
+    /// called and the errors reported by fallible listeners (see
+    /// [`DataTracker::add_try_listener()`](./struct.DataTracker.html#method.add_try_listener))
+    /// are returned. A panicking callback is reported as a
+    /// [`ListenerPanic`](./struct.ListenerPanic.html) error.
+    pub fn try_commit(mut self) -> Result<bool, Vec<ListenerError>> {
+        let (changed, errors) = self.finish(None, true);
+        if errors.is_empty() {
+            Ok(changed)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Return `true` if the data currently differs from its value when this
+    /// `Modifier` was created.
+    ///
+    /// This is the same check performed on drop, including the hints given
+    /// with `mark_dirty()` and `mark_clean()`, so a modification which is
+    /// later reverted within the same scope reports `false`. The check is
+    /// made whenever a copy of the original data exists, see `original()`.
+    ///
+    /// If nothing observed the tracker when the `Modifier` was created, or
+    /// for a `nested()` `Modifier`, there is no copy. A change cannot be
+    /// detected then and, since no listener will be called for it, this
+    /// returns `false` whatever the modifications, like `commit()`.
+    pub fn changed(&self) -> bool {
+        self.orig_copy.as_ref().is_some_and(|orig_copy| self.is_changed_from(orig_copy))
+    }
+
+    /// Return the data as it was when this `Modifier` was created.
+    ///
+    /// This is the value which will be passed to the listeners as the old
+    /// value. Returns `None` if no copy was made, because nothing observed
+    /// the tracker when the `Modifier` was created, or for a `nested()`
+    /// `Modifier`.
+    pub fn original(&self) -> Option<&T> {
+        self.orig_copy.as_ref()
+    }
+
+    /// Declare that the data was changed, so that the listeners are called
+    /// without comparing it to the original.
+    ///
+    /// This avoids an expensive comparison when the outcome is known. Like
+    /// the comparison, this has no effect if no copy of the original data was
+    /// made, i.e. if nothing observed the tracker when the `Modifier` was
+    /// created, or for a `nested()` `Modifier`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = Some(true);
+    }
+
+    /// Declare that the data was not changed, so that neither a comparison
+    /// nor a notification is made.
+    ///
+    /// The last call to `mark_dirty()` or `mark_clean()` wins.
+    pub fn mark_clean(&mut self) {
+        self.dirty = Some(false);
+    }
+
+    fn is_changed_from(&self, orig_copy: &T) -> bool {
+        match self.dirty {
+            Some(dirty) => dirty,
+            None => self.inner_ref.changed_from(orig_copy),
+        }
+    }
+}
+
+impl<'a, T, K> std::ops::Deref for Modifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner_ref.value
+    }
+}
+
+impl<'a, T, K> std::ops::DerefMut for Modifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner_ref.value
+    }
+}
+
+impl<'a, T, K> Drop for Modifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        self.finish(None, false);
+    }
+}
+
+/// Allow viewing and modifying data owned by `DataTracker` without notifying
+/// listeners.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::modify_silent()`](./struct.DataTracker.html#method.modify_silent).
+/// Unlike [`Modifier`](./struct.Modifier.html), no copy of the original data
+/// is made and no equality check is performed when this is dropped.
+pub struct SilentModifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    inner_ref: &'a mut Inner<T, K>,
+}
+
+impl<'a, T, K> std::ops::Deref for SilentModifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner_ref.value
+    }
+}
+
+impl<'a, T, K> std::ops::DerefMut for SilentModifier<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner_ref.value
+    }
+}
+
+/// Tracks changes to data and notifies listeners.
+///
+/// The data to be tracked is type `T`.
+///
+/// Callbacks are stored in a `HashMap` (a `BTreeMap` with the `no_std`
+/// feature) with keys of type `K`. Nevertheless,
+/// they are called in a deterministic order: by ascending priority and then
+/// in the order in which they were added.
+///
+/// The tracker carries an identifier of type `I`, which defaults to `()`,
+/// see `new_with_id()`.
+///
+/// See the [module-level documentation](./) for more details.
+pub struct DataTracker<T, K, I = ()>
+    where T: Clone,
+          K: ListenerKey
+{
+    inner: Inner<T, K>,
+    id: I,
+}
+
+impl<T, K> DataTracker<T, K>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Create a new `DataTracker` which takes ownership
+    /// of the data of type `T`.
+    ///
+    /// Callbacks are registered via a key of type `K`.
+    pub fn new(value: T) -> DataTracker<T, K>
+        where T: ChangeDetect
+    {
+        DataTracker::new_with_id(value, ())
+    }
+
+    /// Create a new `DataTracker` which notifies on every modification.
+    ///
+    /// No comparison is made, so `T` need not implement `PartialEq`. Every
+    /// `Modifier` which is dropped or committed and every call to `set()` or
+    /// `replace()` calls the listeners, even if the data did not actually
+    /// change. This can over-notify, for example when a `Modifier` is only
+    /// used for reading.
+    pub fn new_always_notify(value: T) -> DataTracker<T, K> {
+        fn never_equal<T>(_: &T, _: &T) -> bool {
+            false
+        }
+        DataTracker {
+            inner: Inner::with_eq(value, Equality::Fn(never_equal)),
+            id: (),
+        }
+    }
+
+    /// Create a new `DataTracker` which uses `comparator` instead of `==` to
+    /// detect changes.
+    ///
+    /// `comparator(old_value, new_value)` returns `true` if the two values are
+    /// considered equal, in which case no change is reported. This allows
+    /// tolerance-based comparisons, and `T` need not implement `PartialEq`.
+    ///
+    /// ```
+    /// # use data_tracker::DataTracker;
+    /// let mut tracked_data: DataTracker<f64, u8> =
+    ///     DataTracker::with_comparator(1.0, |a: &f64, b: &f64| (a - b).abs() < 1e-6);
+    /// tracked_data.add_listener(0, |_: &f64, _: &f64| panic!("jitter"));
+    /// tracked_data.set(1.0 + 1e-9);
+    /// ```
+    pub fn with_comparator<F>(value: T, comparator: F) -> DataTracker<T, K>
+        where F: Fn(&T, &T) -> bool + MaybeSend + MaybeSync + 'static
+    {
+        DataTracker {
+            inner: Inner::with_eq(value, Equality::Comparator(Arc::new(comparator))),
+            id: (),
+        }
+    }
+
+    /// Create a new `DataTracker` with an initial set of callbacks.
+    ///
+    /// This is equivalent to calling `add_listener()` for each entry of
+    /// `listeners`. Since a `HashMap` has no order, the order in which these
+    /// callbacks are called relative to each other is unspecified. Callbacks
+    /// added later are called after them.
+    pub fn new_with_listeners<L>(value: T, listeners: Map<K, L>) -> DataTracker<T, K>
+        where T: ChangeDetect,
+              L: Into<Registration<T>>
+    {
+        let mut inner = Inner::new(value);
+        inner.add_registrations(listeners);
+        DataTracker { inner, id: () }
+    }
+
+    /// Create a new `DataTracker` which counts how often each callback is
+    /// called.
+    ///
+    /// The counts can be queried with `fire_count()`. Without this, no
+    /// counting is done.
+    pub fn with_fire_counts(value: T) -> DataTracker<T, K>
+        where T: ChangeDetect
+    {
+        let mut inner = Inner::new(value);
+        inner.listeners.count_fires = true;
+        DataTracker { inner, id: () }
+    }
+
+    /// Create a new `DataTracker` which records the last `capacity` changes.
+    ///
+    /// Every detected change is recorded, whether or not any listeners are
+    /// registered, and can be inspected with `recent_changes()`. Note that
+    /// this means a copy of the data is made for every `Modifier`.
+    pub fn with_journal(value: T, capacity: usize) -> DataTracker<T, K>
+        where T: ChangeDetect
+    {
+        let mut inner = Inner::new(value);
+        inner.journal = Some(Journal::new(capacity));
+        DataTracker { inner, id: () }
+    }
+
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Create a new `DataTracker` which is identified by `id`.
+    ///
+    /// The identifier is passed to callbacks registered with
+    /// `add_id_listener()`, which is useful when several trackers feed the
+    /// same handler. It can also carry metadata which is fixed for the
+    /// lifetime of the tracker, such as the source of the data, so the
+    /// callbacks need not capture it themselves.
+    pub fn new_with_id(value: T, id: I) -> DataTracker<T, K, I>
+        where T: ChangeDetect
+    {
+        DataTracker {
+            inner: Inner::new(value),
+            id,
+        }
+    }
+
+    /// Return the identifier given to `new_with_id()`.
+    pub fn id(&self) -> &I {
+        &self.id
+    }
+
+    /// Add a callback which also receives the identifier of this tracker.
+    ///
+    /// On every detected change, `callback(&id, old_value, new_value)` is
+    /// called. The callback holds its own copy of the identifier.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_id_listener<F>(&mut self, key: K, callback: F) -> Option<Box<dyn OnChanged<T>>>
+        where I: Clone + MaybeSend + 'static,
+              F: Fn(&I, &T, &T) + MaybeSend + 'static
+    {
+        let id = self.id.clone();
+        let callback = move |old_value: &T, new_value: &T| callback(&id, old_value, new_value);
+        self.inner.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Return the most recent changes as `(old_value, new_value)` pairs,
+    /// oldest first.
+    ///
+    /// This is empty unless the tracker was created with `with_journal()`.
+    pub fn recent_changes(&self) -> &[(T, T)] {
+        match self.inner.journal {
+            Some(ref journal) => journal.recent(),
+            None => &[],
+        }
+    }
+
+    /// Apply the new values of recorded `changes` in order, as if set with
+    /// `set()`.
+    ///
+    /// This reproduces a history obtained from `recent_changes()` of another
+    /// tracker, possibly after storing it, to reconstruct how the data
+    /// evolved. The listeners are called for each value which differs from
+    /// the current one, so the usual equality suppression applies. The old
+    /// values of `changes` are not used.
+    pub fn replay(&mut self, changes: &[(T, T)]) {
+        for (_, new_value) in changes {
+            self.set(new_value.clone());
+        }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// Callbacks are called in the order in which they were added. Replacing
+    /// the callback of an existing `key` moves it to the end of this order.
+    /// The callback is registered with priority `0`, see
+    /// `add_listener_with_priority()`.
+    ///
+    /// The callback is boxed internally, so a closure can be passed directly.
+    /// Use `add_boxed_listener()` for a callback which is already boxed.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&mut self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.add_boxed_listener(key, Box::new(callback))
+    }
+
+    /// Add a callback and call it once right away, with the current value as
+    /// both the old and the new value.
+    ///
+    /// This lets a consumer initialize itself from the current state, after
+    /// which the callback is called for changes like any other. Returns like
+    /// `add_listener()`.
+    pub fn add_listener_and_fire<C>(&mut self,
+                                    key: K,
+                                    callback: C)
+                                    -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        callback.on_changed(&self.inner.value, &self.inner.value);
+        self.add_listener(key, callback)
+    }
+
+    /// Add a callback only if no callback is registered with the `key`.
+    ///
+    /// Returns `true` if the callback was added. Otherwise, the existing
+    /// callback is kept and `callback` is dropped. This allows several code
+    /// paths to register the same subscription idempotently.
+    pub fn add_listener_if_absent<C>(&mut self, key: K, callback: C) -> bool
+        where C: OnChanged<T> + 'static
+    {
+        self.inner.listeners.insert_if_absent(key, 0, false, Box::new(callback))
+    }
+
+    /// Add a callback, returning `Err(KeyExists)` if a callback is already
+    /// registered with the `key`.
+    ///
+    /// Unlike `add_listener()`, an existing callback is never replaced, so
+    /// reusing a key by accident is reported rather than silently dropping
+    /// the previous callback. The error carries the `key` back to the caller,
+    /// and `callback` is dropped.
+    pub fn add_listener_strict<C>(&mut self, key: K, callback: C) -> Result<(), KeyExists<K>>
+        where C: OnChanged<T> + 'static
+    {
+        if self.inner.listeners.contains(&key) {
+            return Err(KeyExists { key });
+        }
+        self.inner.listeners.insert(key, 0, false, Box::new(callback));
+        Ok(())
+    }
+
+    /// Set a callback which is called after the keyed callbacks on every
+    /// detected change.
+    ///
+    /// The default listener does not occupy a key and is called last,
+    /// whatever the priorities of the keyed callbacks, even if none are
+    /// registered. There is at most one, so a previous default listener is
+    /// returned as `Some(callback)`. Otherwise, `None` is returned.
+    pub fn set_default_listener<C>(&mut self, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        let callback: Box<dyn OnChanged<T>> = Box::new(callback);
+        self.inner.default_listener.replace(Exclusive(callback)).map(Exclusive::into_inner)
+    }
+
+    /// Remove the callback set with `set_default_listener()` and return it.
+    pub fn take_default_listener(&mut self) -> Option<Box<dyn OnChanged<T>>> {
+        self.inner.default_listener.take().map(Exclusive::into_inner)
+    }
+
+    /// Add a callback which belongs to the group `group`.
+    ///
+    /// This behaves like `add_listener()`, but the callback can also be
+    /// removed together with all others of its group by `remove_group()`,
+    /// for example to unsubscribe a whole feature at once. A callback belongs
+    /// to at most one group.
+    pub fn add_listener_in_group<C>(&mut self,
+                                    group: u64,
+                                    key: K,
+                                    callback: C)
+                                    -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        let mut listener = self.inner.listeners.new_listener(0, false, Box::new(callback));
+        listener.group = Some(group);
+        self.inner.listeners.fn_map.insert(key, listener).map(|l| l.callback.into_inner())
+    }
+
+    /// Remove all callbacks of the group `group`, returning how many were
+    /// removed.
+    pub fn remove_group(&mut self, group: u64) -> usize {
+        self.inner.listeners.apply_removals();
+        let count = self.inner.listeners.fn_map.len();
+        self.inner.listeners.fn_map.retain(|_, l| l.group != Some(group));
+        count - self.inner.listeners.fn_map.len()
+    }
+
+    /// Like `add_listener()`, but take a callback which is already boxed.
+    pub fn add_boxed_listener(&mut self,
+                              key: K,
+                              callback: Box<dyn OnChanged<T>>)
+                              -> Option<Box<dyn OnChanged<T>>> {
+        self.inner.listeners.insert(key, 0, false, callback)
+    }
+
+    /// Add a callback and return the `DataTracker`, for chained construction.
+    ///
+    /// This is `add_listener()` in builder style:
+    ///
+    /// ```
+    /// # use data_tracker::DataTracker;
+    /// let tracked_data = DataTracker::new(1)
+    ///     .with_listener("a", |_: &i32, _: &i32| println!("a"))
+    ///     .with_listener("b", |_: &i32, _: &i32| println!("b"));
+    /// assert!(tracked_data.listener_count() == 2);
+    /// ```
+    pub fn with_listener<C>(mut self, key: K, callback: C) -> DataTracker<T, K, I>
+        where C: OnChanged<T> + 'static
+    {
+        self.inner.listeners.insert(key, 0, false, Box::new(callback));
+        self
+    }
+
+    /// Add a closure that will be called just after a data change is detected.
+    ///
+    /// This behaves like `add_listener()`, but takes the closure directly.
+    /// Since the argument types are fixed by this signature, they need not be
+    /// annotated:
+    ///
+    /// ```
+    /// # use data_tracker::DataTracker;
+    /// let mut tracked_data = DataTracker::new(1);
+    /// tracked_data.add_fn(0, |old_value, new_value| {
+    ///     println!("changed {} -> {}", old_value, new_value);
+    /// });
+    /// ```
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_fn<F>(&mut self, key: K, f: F) -> Option<Box<dyn OnChanged<T>>>
+        where F: Fn(&T, &T) + MaybeSend + 'static
+    {
+        self.inner.listeners.insert(key, 0, false, Box::new(f))
+    }
+
+    /// Register `callback` with `key`, telling whether a callback was
+    /// displaced.
+    ///
+    /// This behaves like `add_listener()`, but the result spells out whether
+    /// the callback was freshly inserted or replaced a previous one.
+    pub fn replace_listener<C>(&mut self, key: K, callback: C) -> ListenerSlot<T>
+        where C: OnChanged<T> + 'static
+    {
+        match self.inner.listeners.insert(key, 0, false, Box::new(callback)) {
+            Some(previous) => ListenerSlot::Replaced(previous),
+            None => ListenerSlot::Inserted,
+        }
+    }
+
+    /// Add a callback with a given priority.
+    ///
+    /// When a change is detected, callbacks are called in ascending order of
+    /// `priority`. Callbacks with equal priority are called in the order in
+    /// which they were added.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener_with_priority<C>(&mut self,
+                                         key: K,
+                                         priority: i32,
+                                         callback: C)
+                                         -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.inner.listeners.insert(key, priority, false, Box::new(callback))
+    }
+
+    /// Add a callback that will be called at most once.
+    ///
+    /// The callback is called on the next detected change and then removed
+    /// automatically. Until then, it can be removed with `remove_listener()`
+    /// like any other callback.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_once_listener<C>(&mut self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.inner.listeners.insert(key, 0, true, Box::new(callback))
+    }
+
+    /// Return a `Changes` which asynchronously yields each detected change.
+    ///
+    /// This registers a callback under `key` which clones the old and the new
+    /// values into a queue, replacing any existing callback with that key.
+    /// The sequence ends when the `DataTracker` is dropped or the callback is
+    /// removed with `remove_listener()`.
+    ///
+    /// ```edition2018
+    /// # use data_tracker::DataTracker;
+    /// # async fn run(tracked_data: &mut DataTracker<u8, &'static str>) {
+    /// let mut changes = tracked_data.changes("stream");
+    /// while let Some((old_value, new_value)) = changes.next().await {
+    ///     println!("changed {} -> {}", old_value, new_value);
+    /// }
+    /// # }
+    /// ```
+    #[cfg(not(feature = "no_std"))]
+    pub fn changes(&mut self, key: K) -> Changes<T>
+        where T: MaybeSend + 'static
+    {
+        let (sender, changes) = changes::channel();
+        self.inner.listeners.insert(key, 0, false, Box::new(sender));
+        changes
+    }
+
+    /// Add a callback which sends each change down a channel.
+    ///
+    /// On every detected change, the old and the new values are cloned and
+    /// sent as `(old_value, new_value)` through `tx`. Keep in mind that this
+    /// clones the data twice per change. If the receiving end has been
+    /// dropped, the send fails silently.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    #[cfg(not(feature = "no_std"))]
+    pub fn add_sender(&mut self,
+                      key: K,
+                      tx: std::sync::mpsc::Sender<(T, T)>)
+                      -> Option<Box<dyn OnChanged<T>>>
+        where T: MaybeSend + 'static
+    {
+        let callback = move |old_value: &T, new_value: &T| {
+            let _ = tx.send((old_value.clone(), new_value.clone()));
+        };
+        self.inner.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Add a callback which queues each change for the returned
+    /// `BoundedReceiver`, holding at most `capacity` changes.
+    ///
+    /// Unlike with `add_sender()`, the queue cannot grow without limit if the
+    /// receiver falls behind. When it is full, `policy` decides whether the
+    /// oldest queued change or the new one is dropped, and the receiver's
+    /// `dropped_count()` is incremented, which tells how far the consumer
+    /// lags. A `capacity` of zero drops every change.
+    ///
+    /// This registers a callback under `key`, replacing any existing callback
+    /// with that key.
+    #[cfg(not(feature = "no_std"))]
+    pub fn add_bounded_sender(&mut self,
+                              key: K,
+                              capacity: usize,
+                              policy: DropPolicy)
+                              -> BoundedReceiver<T>
+        where T: MaybeSend + 'static
+    {
+        let (sender, receiver) = bounded::channel(capacity, policy);
+        self.inner.listeners.insert(key, 0, false, Box::new(sender));
+        receiver
+    }
+
+    /// Add a callback which is only called when the returned `Coalescer` is
+    /// flushed.
+    ///
+    /// A listener registered under `key` collects the changes. Calling
+    /// [`Coalescer::flush()`](./struct.Coalescer.html#method.flush) passes the
+    /// old value from before the first collected change and the latest value
+    /// to `callback`, in a single call. This suits slow consumers of frequent
+    /// changes. Removing `key` stops collecting changes.
+    ///
+    /// This is not available with the `no_std` feature.
+    #[cfg(not(feature = "no_std"))]
+    pub fn add_coalescing_listener<C>(&mut self, key: K, callback: C) -> Coalescer<T>
+        where T: MaybeSend + 'static,
+              C: OnChanged<T> + 'static
+    {
+        let (sender, coalescer) = coalesce::coalescing(Box::new(callback), self.inner.eq.clone());
+        self.inner.listeners.insert(key, 0, false, Box::new(sender));
+        coalescer
+    }
+
+    /// Add a callback which is only called once changes have settled.
+    ///
+    /// The callback is called from the timer thread after `duration` has
+    /// elapsed without any further change. It receives the value from
+    /// before the first of the collapsed changes and the latest value.
+    /// Dropping the `DataTracker` (or removing the callback) cancels a pending
+    /// call.
+    ///
+    /// This requires the `timer` feature and is not available with the
+    /// `no_send` or the `no_std` feature.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    #[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+    pub fn add_debounced_listener<C>(&mut self,
+                                     key: K,
+                                     duration: std::time::Duration,
+                                     callback: C)
+                                     -> Option<Box<dyn OnChanged<T>>>
+        where T: Send + 'static,
+              C: OnChanged<T> + 'static
+    {
+        let debounced = timed::Debounced::new(duration, callback);
+        self.inner.listeners.insert(key, 0, false, Box::new(debounced))
+    }
+
+    /// Add a callback which is called at most once per `min_interval`.
+    ///
+    /// The callback is called from the timer thread. A change is
+    /// delivered right away if the previous delivery was at least
+    /// `min_interval` ago. Otherwise, it is held back until then, collapsed
+    /// with any further changes: the callback receives the value from before
+    /// the first of them and the latest value. Unlike with
+    /// `add_debounced_listener()`, deliveries continue during sustained
+    /// changes. Dropping the `DataTracker` (or removing the callback) cancels
+    /// a pending call.
+    ///
+    /// This requires the `timer` feature and is not available with the
+    /// `no_send` or the `no_std` feature.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    #[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+    pub fn add_rate_limited_listener<C>(&mut self,
+                                        key: K,
+                                        min_interval: std::time::Duration,
+                                        callback: C)
+                                        -> Option<Box<dyn OnChanged<T>>>
+        where T: Send + 'static,
+              C: OnChanged<T> + 'static
+    {
+        let rate_limited = timed::RateLimited::new(min_interval, callback);
+        self.inner.listeners.insert(key, 0, false, Box::new(rate_limited))
+    }
+
+    /// Add a callback which receives all changes within a time window at
+    /// once.
+    ///
+    /// The first change after a delivery opens a window of length `window`.
+    /// The `(old_value, new_value)` pairs of all changes until it closes are
+    /// collected and then passed to the callback as one slice, from the timer
+    /// thread. Unlike with `add_debounced_listener()`, no change is dropped.
+    /// Changes collected when the `DataTracker` is dropped (or the callback
+    /// is removed) are delivered right away.
+    ///
+    /// This requires the `timer` feature and is not available with the
+    /// `no_send` or the `no_std` feature.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    #[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+    pub fn add_windowed_listener<F>(&mut self,
+                                    key: K,
+                                    window: std::time::Duration,
+                                    callback: F)
+                                    -> Option<Box<dyn OnChanged<T>>>
+        where T: Send + 'static,
+              F: Fn(&[(T, T)]) + Send + 'static
+    {
+        let windowed = timed::Windowed::new(window, callback);
+        self.inner.listeners.insert(key, 0, false, Box::new(windowed))
+    }
+
+    /// Add a callback which runs on `executor` rather than inline.
+    ///
+    /// On every change, the old and new values are cloned and a job calling
+    /// the callback is submitted to `executor`, such as a
+    /// [`WorkerPool`](./struct.WorkerPool.html) or a closure passing the job to
+    /// another thread pool. The change is thus reported without waiting for
+    /// the callback. Jobs may run concurrently, so the callback may see
+    /// changes out of order, and there is no ordering between offloaded
+    /// callbacks or relative to the other callbacks, which is why the callback
+    /// must be `Sync`. A panic of the callback is left to the executor, where
+    /// a `WorkerPool` catches it and goes on running the later jobs.
+    ///
+    /// This is not available with the `no_send` or the `no_std` feature.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    #[cfg(not(any(feature = "no_send", feature = "no_std")))]
+    pub fn add_offloaded_listener<E, C>(&mut self,
+                                        key: K,
+                                        executor: E,
+                                        callback: C)
+                                        -> Option<Box<dyn OnChanged<T>>>
+        where T: Send + 'static,
+              E: Executor + 'static,
+              C: OnChanged<T> + Sync + 'static
+    {
+        let offloaded = offload::Offloaded::new(executor, std::sync::Arc::new(callback));
+        self.inner.listeners.insert(key, 0, false, Box::new(offloaded))
+    }
+
+    /// Add a fallible callback that will be called just after a data change is
+    /// detected.
+    ///
+    /// Errors returned by the callback are collected by
+    /// [`Modifier::try_commit()`](./struct.Modifier.html#method.try_commit).
+    /// All other notification paths discard them.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_try_listener<E, C>(&mut self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where T: 'static,
+              E: Into<ListenerError> + 'static,
+              C: TryOnChanged<T, E> + 'static
+    {
+        let fallible = Fallible { callback: Box::new(callback) };
+        self.inner.listeners.insert(key, 0, false, Box::new(fallible))
+    }
+
+    /// Add a callback which is only called for changes accepted by `predicate`.
+    ///
+    /// When a change is detected, `predicate(old_value, new_value)` is called
+    /// and `callback` only runs if it returns `true`. The predicate is never
+    /// called with values which are equal according to the tracker, see
+    /// `with_comparator()`, so a filtered callback is skipped by
+    /// `notify_all()`.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_filtered_listener<P, C>(&mut self,
+                                       key: K,
+                                       predicate: P,
+                                       callback: C)
+                                       -> Option<Box<dyn OnChanged<T>>>
+        where T: 'static,
+              P: Fn(&T, &T) -> bool + MaybeSend + 'static,
+              C: OnChanged<T> + 'static
+    {
+        let filtered = Filtered {
+            predicate,
+            callback: Box::new(callback),
+            eq: self.inner.eq.clone(),
+        };
+        self.inner.listeners.insert(key, 0, false, Box::new(filtered))
+    }
+
+    /// Add a callback which is only called when the data changes from `from`
+    /// to `to`.
+    ///
+    /// This is a shorthand for `add_filtered_listener()` suited to state
+    /// machines, such as reacting to a transition from `Idle` to `Running`.
+    /// Several transition listeners can be added under different keys.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_transition_listener<C>(&mut self,
+                                      key: K,
+                                      from: T,
+                                      to: T,
+                                      callback: C)
+                                      -> Option<Box<dyn OnChanged<T>>>
+        where T: PartialEq + MaybeSend + 'static,
+              C: OnChanged<T> + 'static
+    {
+        let predicate = move |old_value: &T, new_value: &T| *old_value == from && *new_value == to;
+        self.add_filtered_listener(key, predicate, callback)
+    }
+
+    /// Add a callback which holds only a weak reference to `target`.
+    ///
+    /// On every detected change, `callback(&target, old_value, new_value)` is
+    /// called with the upgraded reference. The tracker thus does not keep
+    /// `target` alive. Once `target` has been dropped, the callback is
+    /// silently removed, at the latest before the next notification. Until
+    /// then it still counts towards `listener_count()`.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_weak_listener<S, F>(&mut self,
+                                   key: K,
+                                   target: &Arc<S>,
+                                   callback: F)
+                                   -> Option<Box<dyn OnChanged<T>>>
+        where S: 'static,
+              F: Fn(&S, &T, &T) + MaybeSend + 'static,
+              Weak<S>: MaybeSend,
+              T: 'static
+    {
+        let weak = WeakListener {
+            target: Arc::downgrade(target),
+            callback,
+        };
+        self.inner.listeners.insert(key, 0, false, Box::new(weak))
+    }
+
+    /// Derive a tracker whose value is `f` applied to this tracker's value.
+    ///
+    /// This returns a `SharedTracker` rather than a `DataTracker`: the
+    /// derived tracker is updated by a callback of this tracker while the
+    /// caller holds it, so it has to be shared, and a `DataTracker` can only
+    /// be modified through `&mut`. Its listeners are registered with
+    /// `SharedTracker::add_listener()` and its value read with `get()`.
+    ///
+    /// The returned `SharedTracker` starts with `f(value)`. A callback
+    /// registered with `key` recomputes the derived value after every change
+    /// of this tracker and sets it on the derived tracker, whose listeners are
+    /// only called if the derived value actually changed. Removing `key`
+    /// detaches the derived tracker, which then keeps its last value.
+    ///
+    /// If a previous callback exists with the `key`, it is replaced.
+    #[cfg(not(feature = "no_std"))]
+    pub fn map<U, K2, F>(&mut self, key: K, f: F) -> SharedTracker<U, K2>
+        where U: Clone + PartialEq + MaybeSend + 'static,
+              K2: ListenerKey + MaybeSend + 'static,
+              F: Fn(&T) -> U + MaybeSend + 'static
+    {
+        let derived = SharedTracker::new(f(&self.inner.value));
+        let target = derived.clone();
+        let callback = move |_: &T, new_value: &T| target.set(f(new_value));
+        self.inner.listeners.insert(key, 0, false, Box::new(callback));
+        derived
+    }
+
+    /// Copy every change of this tracker into `other`.
+    ///
+    /// `other` is set to the current value right away, and a callback
+    /// registered with `key` sets it to the new value after every change.
+    /// Setting a `SharedTracker` to a value equal to its current one does not
+    /// notify its listeners, so a binding in the other direction (made by a
+    /// listener of `other`) settles instead of looping forever. Removing `key`
+    /// ends the binding.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    #[cfg(not(feature = "no_std"))]
+    pub fn pipe_to<K2>(&mut self,
+                       key: K,
+                       other: SharedTracker<T, K2>)
+                       -> Option<Box<dyn OnChanged<T>>>
+        where T: PartialEq + MaybeSend + 'static,
+              K2: ListenerKey + MaybeSend + 'static
+    {
+        other.set(self.inner.value.clone());
+        let callback = move |_: &T, new_value: &T| other.set(new_value.clone());
+        self.inner.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    ///
+    /// Like `HashMap::remove()`, the `key` may be any borrowed form of `K`,
+    /// such as a `&str` for `String` keys.
+    pub fn remove_listener<Q>(&mut self, key: &Q) -> Option<Box<dyn OnChanged<T>>>
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.inner.listeners.remove(key)
+    }
+
+    /// Return `true` if a callback is registered with the `key`.
+    ///
+    /// As with `remove_listener()`, the `key` may be any borrowed form of `K`.
+    pub fn contains_listener<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.inner.listeners.contains(key)
+    }
+
+    /// Return how many times the callback registered with the `key` has been
+    /// called.
+    ///
+    /// Returns `None` if no callback is registered with the `key` or the
+    /// tracker was not created with `with_fire_counts()`. Replacing a callback
+    /// starts its count from zero.
+    pub fn fire_count<Q>(&self, key: &Q) -> Option<u64>
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        if !self.inner.listeners.count_fires {
+            return None;
+        }
+        self.inner.listeners.get(key).map(|l| l.fires)
+    }
+
+    /// Skip the callback registered with the `key` until it is enabled again.
+    ///
+    /// Unlike `remove_listener()`, the callback is kept. A disabled callback
+    /// is not called, not counted by `fire_count()` and, for one added with
+    /// `add_once_listener()`, not used up. Returns `false` if no callback is
+    /// registered with the `key`.
+    pub fn disable_listener<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.set_listener_enabled(key, false)
+    }
+
+    /// Call the callback registered with the `key` again after
+    /// `disable_listener()`.
+    ///
+    /// Returns `false` if no callback is registered with the `key`.
+    pub fn enable_listener<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        self.set_listener_enabled(key, true)
+    }
+
+    fn set_listener_enabled<Q>(&mut self, key: &Q, enabled: bool) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        match self.inner.listeners.get_mut(key) {
+            Some(listener) => {
+                listener.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return a reference to the callback registered with the `key`, without
+    /// removing it.
+    ///
+    /// This takes `&mut self` on purpose. Callbacks need only be `Send`, not
+    /// `Sync`, and a `DataTracker` is still `Sync` because its callbacks are
+    /// only reachable through `&mut self`. Returning a callback from `&self`
+    /// would let several threads sharing a `&DataTracker` call the same
+    /// callback at once, which is unsound for a callback that is not `Sync`.
+    /// To check whether a callback is registered through a shared reference,
+    /// or while a `ReadGuard` exists, use `contains_listener()` instead.
+    pub fn peek_listener(&mut self, key: &K) -> Option<&dyn OnChanged<T>> {
+        self.inner.listeners.get_mut(key).map(|l| &**l.callback.get_mut())
+    }
+
+    /// Return an iterator over the keys of all registered callbacks.
+    ///
+    /// The keys are visited in the order in which the callbacks are called.
+    pub fn listener_keys(&self) -> impl Iterator<Item = &K> {
+        self.inner.listeners.ordered().into_iter().map(|(key, _)| key)
+    }
+
+    /// Remove all callbacks.
+    ///
+    /// Returns the number of callbacks that were removed.
+    pub fn clear_listeners(&mut self) -> usize {
+        self.inner.listeners.take().len()
+    }
+
+    /// Replace all callbacks with `listeners` and return the previous ones.
+    ///
+    /// The new callbacks keep their registration: boxed callbacks are
+    /// registered as if by `add_listener()`, so the order in which they are
+    /// called relative to each other is unspecified, and a `Registration`
+    /// keeps its priority, once-flag and group. No callbacks are called while
+    /// swapping, and the returned callbacks can later be reinstalled the same
+    /// way, in their previous order.
+    pub fn set_listeners<L>(&mut self, listeners: Map<K, L>) -> Map<K, Registration<T>>
+        where L: Into<Registration<T>>
+    {
+        let old = self.inner.listeners.take();
+        self.inner.add_registrations(listeners);
+        old.into_iter().map(|(key, listener)| (key, Registration { listener })).collect()
+    }
+
+    /// Convert this tracker into one whose callbacks are registered under
+    /// keys of type `K2`, obtained by calling `f` with each key.
+    ///
+    /// Everything else is kept, including the priorities and the order of the
+    /// callbacks. If `f` maps several keys to the same key, the callback
+    /// called last wins and the others are dropped. The same applies to the
+    /// keys of fold listeners, where it is unspecified which one wins.
+    pub fn map_key<K2, F>(self, f: F) -> DataTracker<T, K2, I>
+        where K2: ListenerKey,
+              F: Fn(K) -> K2
+    {
+        DataTracker {
+            inner: self.inner.map_key(f),
+            id: self.id,
+        }
+    }
+
+    /// Move all callbacks of `other` to this tracker.
+    ///
+    /// The callbacks keep their registration and are called after the
+    /// callbacks of this tracker with the same priority, in their previous
+    /// order. If a key is registered with both trackers, this tracker's
+    /// callback wins: the callback of `other` is not moved but returned with
+    /// its key, so `other` is left without callbacks either way. No callbacks
+    /// are called.
+    pub fn merge_listeners_from<I2>(&mut self,
+                                    other: &mut DataTracker<T, K, I2>)
+                                    -> Map<K, Registration<T>> {
+        let mut theirs: Vec<_> = other.inner.listeners.take().into_iter().collect();
+        theirs.sort_by_key(|(_, l)| (l.priority, l.seq));
+        let mut displaced = Map::new();
+        for (key, listener) in theirs {
+            if self.inner.listeners.contains(&key) {
+                displaced.insert(key, Registration { listener });
+            } else {
+                self.inner.listeners.insert_listener(key, listener);
+            }
+        }
+        displaced
+    }
+
+    /// Remove all callbacks and return them.
+    ///
+    /// Unlike `clear_listeners()`, ownership of the callbacks is handed back
+    /// together with their registration, for example to register them with
+    /// another tracker using `set_listeners()` or `new_with_listeners()`. No
+    /// callbacks are called.
+    pub fn drain_listeners(&mut self) -> Map<K, Registration<T>> {
+        let none: Map<K, Registration<T>> = Map::new();
+        self.set_listeners(none)
+    }
+
+    /// Return the number of registered callbacks.
+    pub fn listener_count(&self) -> usize {
+        self.inner.listeners.len()
+    }
+
+    /// Return `true` if at least one callback is registered.
+    pub fn has_listeners(&self) -> bool {
+        !self.inner.listeners.is_empty()
+    }
+
+    /// Replace the owned data with `value`.
+    ///
+    /// If the new value is not equal to the previous value, the listeners are
+    /// called with references to the old and the new values. Unlike
+    /// `as_tracked_mut()`, no copy of the original data is made.
+    pub fn set(&mut self, value: T) {
+        self.replace(value);
+    }
+
+    /// Replace the owned data with `value`, returning the previous value.
+    ///
+    /// This behaves like `set()` but hands ownership of the displaced value
+    /// back to the caller. The listeners (if the value changed) are called
+    /// before `replace()` returns.
+    pub fn replace(&mut self, value: T) -> T {
+        self.inner.replace(value).0
+    }
+
+    /// Replace the owned data with `f(&current_value)`, returning the
+    /// previous value.
+    ///
+    /// This behaves like `replace()`, for when the new value is computed from
+    /// the current one rather than by modifying it in place. No copy of the
+    /// data is made. The listeners receive the previous and the computed value.
+    pub fn replace_with<F>(&mut self, f: F) -> T
+        where F: FnOnce(&T) -> T
+    {
+        let value = f(&self.inner.value);
+        self.replace(value)
+    }
+
+    /// Replace the owned data with `T::default()`, returning the previous
+    /// value.
+    ///
+    /// This behaves like `replace(T::default())`, so the listeners are called
+    /// if the previous value differs from the default one.
+    pub fn take(&mut self) -> T
+        where T: Default
+    {
+        self.replace(T::default())
+    }
+
+    /// Replace the owned data with `value`, returning `true` if it changed.
+    ///
+    /// This behaves like `set()`, and the listeners receive the old and the
+    /// new values as usual. The result tells whether a change was detected
+    /// and hence the listeners were called, unless notifications are disabled
+    /// with `set_notifications_enabled()`.
+    pub fn replace_if_changed(&mut self, value: T) -> bool {
+        self.inner.replace(value).1
+    }
+
+    /// Call all listeners, including the default listener, even though the
+    /// data has not changed.
+    ///
+    /// The current value is passed as both the old and the new value, so the
+    /// two references given to the callbacks are equal. This is useful when
+    /// listeners depend on external state and need to refresh.
+    pub fn notify_all(&mut self) {
+        if self.inner.enabled {
+            let (_, panic) = self.inner.call_listeners(None, None);
+            resume_panic(panic);
+        }
+    }
+
+    /// Enable or disable all notifications.
+    ///
+    /// While notifications are disabled, no copies of the data are made, no
+    /// changes are detected and no callbacks are called, across any number
+    /// of `Modifier` scopes and calls to `set()`, `replace()` or
+    /// `notify_all()`. This is useful for bulk updates.
+    ///
+    /// When notifications are enabled again and the data differs from its
+    /// value when they were disabled, a single catch-up notification is made
+    /// with these two values. Only that net change is recorded in the journal.
+    pub fn set_notifications_enabled(&mut self, enabled: bool) {
+        if enabled == self.inner.enabled {
+            return;
+        }
+        if enabled {
+            self.inner.enabled = true;
+            if let Some(orig_copy) = self.inner.catch_up.take() {
+                if self.inner.changed_from(&orig_copy) {
+                    self.inner.notify_listeners(&orig_copy);
+                }
+            }
+        } else {
+            if self.inner.is_observed() {
+                self.inner.catch_up = Some(self.inner.value.clone());
+            }
+            self.inner.enabled = false;
+        }
+    }
+
+    /// Install a hook which adjusts the data after every modification.
+    ///
+    /// When a `Modifier` is dropped or committed, and in `set()` and
+    /// `replace()`, `normalizer` is called with the modified data, for
+    /// example to clamp it into range. Only then is the data compared to its
+    /// original value and are the listeners notified, with the normalized
+    /// value. The normalizer runs once per modification: changes it makes
+    /// are not normalized again, and if it restores the original value, no
+    /// change is reported.
+    ///
+    /// ```
+    /// # use data_tracker::DataTracker;
+    /// let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(5);
+    /// tracked_data.set_normalizer(|value: &mut i32| *value = (*value).clamp(0, 10));
+    /// tracked_data.set(42);
+    /// assert!(*tracked_data == 10);
+    /// ```
+    ///
+    /// Any previous normalizer is replaced. Data modified through
+    /// `modify_silent()` or `as_mut()` is not normalized.
+    pub fn set_normalizer<F>(&mut self, normalizer: F)
+        where F: Fn(&mut T) + MaybeSend + MaybeSync + 'static
+    {
+        self.inner.normalizer = Some(Arc::new(normalizer));
+    }
+
+    /// Return a counter which increments whenever the data may have changed.
+    ///
+    /// The version starts at zero and increments whenever a change is
+    /// detected, so a cached version can be compared to find out whether the
+    /// data changed since. A modification which is reverted within the same
+    /// `Modifier` scope detects no change and does not increment it.
+    ///
+    /// The count is conservative: a `Modifier` created while nothing observes
+    /// the tracker makes no copy, so when it is dropped or committed the
+    /// version increments even if the data is unchanged, unless
+    /// `mark_clean()` was called. `nested()` scopes leave it to the outer
+    /// one. Changes made while notifications are disabled increment the
+    /// version too. Only `modify_silent()` and the other unchecked accessors
+    /// such as `as_mut()` leave it alone.
+    pub fn version(&self) -> u64 {
+        self.inner.version
+    }
+
+    /// Return how many `Modifier` scopes ended with a change.
+    ///
+    /// Only `Modifier`s which made a copy of the data are counted, i.e. those
+    /// created while something observed the tracker, and not `nested()`
+    /// ones. Unlike `version()`, changes made with `set()` are not counted.
+    pub fn change_count(&self) -> u64 {
+        self.inner.change_count
+    }
+
+    /// Return how many `Modifier` scopes ended without a change.
+    ///
+    /// This tells how often the copy made for change detection was wasted,
+    /// which points at code calling `as_tracked_mut()` where `as_ref()` would
+    /// do. The same `Modifier`s as for `change_count()` are counted.
+    pub fn noop_count(&self) -> u64 {
+        self.inner.noop_count
+    }
+
+    /// Reset `change_count()` and `noop_count()` to zero.
+    pub fn reset_modifier_counts(&mut self) {
+        self.inner.change_count = 0;
+        self.inner.noop_count = 0;
+    }
+
+    /// Replace the owned data with `value` if the version equals
+    /// `expected_version`.
+    ///
+    /// If it does, this behaves like `set()`. Otherwise the data is left
+    /// alone and `Err(CasError)` is returned, carrying the actual version, so
+    /// an update based on stale data does not clobber a newer change. Since
+    /// modifying a `DataTracker` requires `&mut self`, this only protects
+    /// against interleaved updates within that scope; use
+    /// [`SharedTracker::compare_and_set()`](./struct.SharedTracker.html#method.compare_and_set)
+    /// for updates from several threads.
+    ///
+    /// This relies on `version()`, so it inherits its caveats. Since the
+    /// version is conservative, the call may fail after a modification
+    /// which left the data unchanged. Changes made with `modify_silent()` or
+    /// `as_mut()` do not increment the version and are not detected.
+    pub fn compare_and_set(&mut self, expected_version: u64, value: T) -> Result<(), CasError> {
+        if self.inner.version != expected_version {
+            return Err(CasError { actual: self.inner.version });
+        }
+        self.set(value);
+        Ok(())
+    }
+
+    /// Return `true` unless notifications were disabled with
+    /// `set_notifications_enabled(false)`.
+    pub fn notifications_enabled(&self) -> bool {
+        self.inner.enabled
+    }
+
+    /// Return a `Modifier` which can be used to modify the owned data.
+    ///
+    /// If no listeners are registered, no copy of the data is made.
+    pub fn as_tracked_mut(&mut self) -> Modifier<'_, T, K> {
+        Modifier::new(&mut self.inner)
+    }
+
+    /// Like `as_tracked_mut()`, but use `snapshot` as the copy of the data
+    /// rather than cloning it.
+    ///
+    /// This saves a clone when the caller already holds a copy, for example
+    /// from `get_cloned()`. The caller must make sure that `snapshot` equals
+    /// the current data: it is trusted without a check, and changes are
+    /// detected and reported relative to it, so a stale snapshot causes
+    /// spurious or missed notifications. If nothing observes the tracker,
+    /// `snapshot` is dropped.
+    pub fn as_tracked_mut_with_snapshot(&mut self, snapshot: T) -> Modifier<'_, T, K> {
+        let mut modifier = Modifier::new_without_copy(&mut self.inner);
+        if modifier.inner_ref.is_observed() {
+            modifier.orig_copy = Some(snapshot);
+        }
+        modifier
+    }
+
+    /// Apply several modifications with at most one notification.
+    ///
+    /// A copy of the data is made once, `f` is run against the data, and the
+    /// listeners are then called a single time if the final value differs
+    /// from the copy. Edits which cancel each other out do not notify.
+    pub fn batch<F>(&mut self, f: F)
+        where F: FnOnce(&mut T)
+    {
+        let mut modifier = self.as_tracked_mut();
+        f(&mut modifier);
+    }
+
+    /// Return a `SilentModifier` which can be used to modify the owned data
+    /// without notifying the listeners.
+    pub fn modify_silent(&mut self) -> SilentModifier<'_, T, K> {
+        self.inner.check_readers();
+        SilentModifier { inner_ref: &mut self.inner }
+    }
+
+    /// Return a mutable reference to the owned data, bypassing all change
+    /// detection.
+    ///
+    /// This is an escape hatch for hot loops where copying and comparing the
+    /// data is too expensive. No copy, comparison or normalization is made
+    /// and nothing is recorded, so the listeners never learn about changes
+    /// made this way unless the caller notifies them, typically with
+    /// `notify_all()` once done. Note that `notify_all()` passes the current
+    /// value as both the old and the new value, since the old value is gone,
+    /// and that the version, the journal and the change sink are not updated.
+    /// Forgetting to notify leaves listeners with a stale view of the data.
+    /// Prefer `as_tracked_mut()` or `batch()` unless profiling shows the
+    /// comparison to matter.
+    pub fn get_mut_unchecked(&mut self) -> &mut T {
+        self.inner.check_readers();
+        &mut self.inner.value
+    }
+
+    /// Return a copy of the owned data.
+    ///
+    /// This is a shorthand for `tracked_data.as_ref().clone()`.
+    pub fn get_cloned(&self) -> T {
+        self.inner.value.clone()
+    }
+
+    /// Consume the `DataTracker` and return the owned data.
+    ///
+    /// All callbacks are dropped without being called.
+    pub fn into_inner(self) -> T {
+        self.inner.value
+    }
+}
+
+/// Cloning a `DataTracker` gives an independent tracker with a copy of the
+/// data, which starts without any listeners.
+///
+/// Callbacks generally cannot be cloned, so the listeners, fold listeners
+/// and the change sink are intentionally not carried over. The comparison,
+/// the normalizer, the journal, the version and whether notifications are
+/// enabled are.
+impl<T, K, I> Clone for DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey,
+          I: Clone
+{
+    fn clone(&self) -> DataTracker<T, K, I> {
+        DataTracker {
+            inner: self.inner.clone_without_listeners(),
+            id: self.id.clone(),
+        }
+    }
+}
+
+impl<T, K, I> AsRef<T> for DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    fn as_ref(&self) -> &T {
+        &self.inner.value
+    }
+}
+
+/// Raw mutable access to the owned data, bypassing change tracking.
+///
+/// Changes made this way are never reported to the listeners or recorded in
+/// the journal. This is the minimal form of `modify_silent()`, useful for
+/// example to initialize the data before any listeners are attached.
+impl<T, K, I> AsMut<T> for DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    fn as_mut(&mut self) -> &mut T {
+        self.inner.check_readers();
+        &mut self.inner.value
+    }
+}
+
+/// Read access to the owned data, so that `tracked_data.a` works like
+/// `tracked_data.as_ref().a`.
+///
+/// Methods of `DataTracker` take precedence over methods of `T` of the same
+/// name. Use `as_ref()` (or `(*tracked_data).method()`) to call those. There
+/// is no `DerefMut`, since modifications must go through `as_tracked_mut()`.
+impl<T, K, I> std::ops::Deref for DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::DataTracker;
+
+    #[test]
+    #[allow(clippy::toplevel_ref_arg, clippy::assign_op_pattern)]
+    fn track_struct() {
+        #[derive(Clone, PartialEq)]
+        struct MyData {
+            a: u8,
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(MyData { a: 1 });
+
+        let cc2 = change_count.clone();
+        let key = 0;
+        tracked_data.add_listener(key,
+                                  Box::new(move |_: &MyData, _: &MyData| {
+                                      let ref mut data = *cc2.lock().unwrap();
+                                      *data = *data + 1;
+                                  }));
+
+        assert!(*change_count.lock().unwrap() == 0);
+
+        {
+            let _x = tracked_data.as_ref();
+        }
+        assert!(*change_count.lock().unwrap() == 0);
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            x.a = 10;
+        }
+        assert!(*change_count.lock().unwrap() == 1);
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            x.a += 10;
+        }
+        assert!(*change_count.lock().unwrap() == 2);
+
+        assert!(tracked_data.as_ref().a == 20);
+        assert!(*change_count.lock().unwrap() == 2);
+
+        tracked_data.remove_listener(&key);
+    }
+
+    #[test]
+    #[allow(clippy::toplevel_ref_arg, clippy::assign_op_pattern)]
+    fn track_enum() {
+
+        #[derive(Clone, PartialEq)]
+        enum MyEnum {
+            FirstValue,
+            SecondValue,
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(MyEnum::FirstValue);
+
+        let cc2 = change_count.clone();
+        let key = 0;
+        tracked_data.add_listener(key,
+                                  Box::new(move |_: &MyEnum, _: &MyEnum| {
+                                      let ref mut data = *cc2.lock().unwrap();
+                                      *data = *data + 1;
+                                  }));
+
+        assert!(*change_count.lock().unwrap() == 0);
+
+        {
+            let _x = tracked_data.as_ref();
+        }
+        assert!(*change_count.lock().unwrap() == 0);
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = MyEnum::SecondValue;
+        }
+        assert!(*change_count.lock().unwrap() == 1);
+
+        assert!(tracked_data.as_ref() == &MyEnum::SecondValue);
+        assert!(*change_count.lock().unwrap() == 1);
+
+        tracked_data.remove_listener(&key);
+    }
+
+    #[test]
+    #[allow(clippy::toplevel_ref_arg, clippy::bool_comparison)]
+    fn callback_arg_order() {
+
+        #[derive(Clone, PartialEq)]
+        enum MyEnum {
+            FirstValue,
+            SecondValue,
+        }
+
+        let mut tracked_data = DataTracker::new(MyEnum::FirstValue);
+
+        let did_run = Arc::new(Mutex::new(false));
+        let did_run_clone = did_run.clone();
+
+        tracked_data.add_listener(0,
+                                  Box::new(move |old_value: &MyEnum, new_value: &MyEnum| {
+                                      assert!(old_value == &MyEnum::FirstValue);
+                                      assert!(new_value == &MyEnum::SecondValue);
                                       let ref mut data = *did_run_clone.lock().unwrap();
                                       *data = true;
                                   }));
         {
             let mut x = tracked_data.as_tracked_mut();
-            *x = MyEnum::SecondValue;
+            *x = MyEnum::SecondValue;
+        }
+
+        assert!(*did_run.lock().unwrap() == true);
+    }
+
+    #[test]
+    fn set_value() {
+
+        #[derive(Clone, PartialEq)]
+        enum MyEnum {
+            FirstValue,
+            SecondValue,
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(MyEnum::FirstValue);
+
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &MyEnum, new_value: &MyEnum| {
+                                      assert!(old_value == &MyEnum::FirstValue);
+                                      assert!(new_value == &MyEnum::SecondValue);
+                                      let data = &mut *cc2.lock().unwrap();
+                                      *data += 1;
+                                  });
+
+        tracked_data.set(MyEnum::SecondValue);
+        assert!(*change_count.lock().unwrap() == 1);
+        assert!(tracked_data.as_ref() == &MyEnum::SecondValue);
+
+        // Setting an equal value does not notify.
+        tracked_data.set(MyEnum::SecondValue);
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn replace_value() {
+        let did_run = Arc::new(Mutex::new(false));
+        let did_run_clone = did_run.clone();
+        let mut tracked_data = DataTracker::new(vec![1, 2, 3]);
+
+        tracked_data.add_listener(0,
+                                  move |old_value: &Vec<i32>, new_value: &Vec<i32>| {
+                                      assert!(old_value == &vec![1, 2, 3]);
+                                      assert!(new_value == &vec![4]);
+                                      let data = &mut *did_run_clone.lock().unwrap();
+                                      *data = true;
+                                  });
+
+        let old = tracked_data.replace(vec![4]);
+        assert!(*did_run.lock().unwrap());
+        assert!(old == vec![1, 2, 3]);
+        assert!(tracked_data.as_ref() == &vec![4]);
+    }
+
+    #[test]
+    fn modify_silent() {
+
+        #[derive(Clone, PartialEq)]
+        struct MyData {
+            a: u8,
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(MyData { a: 1 });
+
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |_: &MyData, _: &MyData| {
+                                      let data = &mut *cc2.lock().unwrap();
+                                      *data += 1;
+                                  });
+
+        {
+            let mut x = tracked_data.modify_silent();
+            x.a = 10;
+        }
+        assert!(*change_count.lock().unwrap() == 0);
+        assert!(tracked_data.as_ref().a == 10);
+    }
+
+    #[test]
+    fn listener_count() {
+        let mut tracked_data = DataTracker::new(1);
+        assert!(tracked_data.listener_count() == 0);
+        assert!(!tracked_data.has_listeners());
+
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        tracked_data.add_listener(1, |_: &i32, _: &i32| {});
+        assert!(tracked_data.listener_count() == 2);
+        assert!(tracked_data.has_listeners());
+
+        tracked_data.remove_listener(&0);
+        tracked_data.remove_listener(&1);
+        assert!(tracked_data.listener_count() == 0);
+        assert!(!tracked_data.has_listeners());
+    }
+
+    #[test]
+    fn clear_listeners() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        tracked_data.add_listener(1, |_: &i32, _: &i32| {});
+
+        assert!(tracked_data.clear_listeners() == 2);
+        assert!(!tracked_data.has_listeners());
+        assert!(tracked_data.clear_listeners() == 0);
+    }
+
+    #[test]
+    fn contains_listener() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+
+        assert!(tracked_data.contains_listener(&0));
+        assert!(!tracked_data.contains_listener(&1));
+        // Checking does not remove the callback.
+        assert!(tracked_data.contains_listener(&0));
+    }
+
+    #[test]
+    fn listener_keys() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener("a", |_: &i32, _: &i32| {});
+        tracked_data.add_listener("b", |_: &i32, _: &i32| {});
+
+        let keys: Vec<&str> = tracked_data.listener_keys().cloned().collect();
+        assert!(keys == vec!["a", "b"]);
+    }
+
+    #[test]
+    fn listener_insertion_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+
+        for &key in &["c", "a", "d", "b"] {
+            let calls2 = calls.clone();
+            tracked_data.add_listener(key,
+                                      move |_: &i32, _: &i32| {
+                                          calls2.lock().unwrap().push(key);
+                                      });
+        }
+        // Replacing a callback moves it to the end.
+        let calls2 = calls.clone();
+        assert!(tracked_data.add_listener("a",
+                                          move |_: &i32, _: &i32| {
+                                              calls2.lock().unwrap().push("a");
+                                          })
+            .is_some());
+
+        tracked_data.set(2);
+        assert!(*calls.lock().unwrap() == vec!["c", "d", "b", "a"]);
+        let keys: Vec<&str> = tracked_data.listener_keys().cloned().collect();
+        assert!(keys == vec!["c", "d", "b", "a"]);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0,
+                                  |_: &i32, _: &i32| {
+                                      panic!("listener must not be called");
+                                  });
+        assert!(tracked_data.into_inner() == 1);
+    }
+
+    #[test]
+    fn notify_all() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      assert!(old_value == new_value);
+                                      let data = &mut *cc2.lock().unwrap();
+                                      *data += 1;
+                                  });
+
+        tracked_data.notify_all();
+        tracked_data.notify_all();
+        assert!(*change_count.lock().unwrap() == 2);
+    }
+
+    #[test]
+    fn modifier_changed() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+
+        let mut x = tracked_data.as_tracked_mut();
+        assert!(!x.changed());
+        *x = 2;
+        assert!(x.changed());
+        *x = 1;
+        assert!(!x.changed());
+    }
+
+    #[test]
+    fn modifier_changed_without_copy() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        let mut x = tracked_data.as_tracked_mut();
+        *x = 2;
+        assert!(x.original().is_none());
+        assert!(!x.changed());
+        assert!(!x.commit());
+
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        let mut x = tracked_data.as_tracked_mut();
+        x.mark_dirty();
+        assert!(x.changed());
+        assert!(!x.nested().changed());
+    }
+
+    #[test]
+    fn try_commit() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        tracked_data.add_try_listener(1,
+                                      |_: &i32, new_value: &i32| {
+                                          if *new_value < 0 {
+                                              Err("negative value")
+                                          } else {
+                                              Ok(())
+                                          }
+                                      });
+
+        {
+            let x = tracked_data.as_tracked_mut();
+            assert!(!x.try_commit().unwrap());
+        }
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 2;
+            assert!(x.try_commit().unwrap());
+        }
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = -1;
+            let errors = x.try_commit().unwrap_err();
+            assert!(errors.len() == 1);
+            assert!(errors[0].to_string() == "negative value");
+        }
+    }
+
+    #[test]
+    fn commit() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |_: &i32, _: &i32| {
+                                      let data = &mut *cc2.lock().unwrap();
+                                      *data += 1;
+                                  });
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 2;
+            assert!(x.commit());
+            // Listeners are called by commit(), not when leaving the scope.
+            assert!(*change_count.lock().unwrap() == 1);
+        }
+        assert!(*change_count.lock().unwrap() == 1);
+
+        assert!(!tracked_data.as_tracked_mut().commit());
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn listener_priority() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+
+        for &(key, priority) in &[("repaint", 10), ("log_a", 0), ("layout", -5), ("log_b", 0)] {
+            let calls2 = calls.clone();
+            tracked_data.add_listener_with_priority(key,
+                                                    priority,
+                                                    move |_: &i32, _: &i32| {
+                                                        calls2.lock().unwrap().push(key);
+                                                    });
+        }
+
+        tracked_data.set(2);
+        assert!(*calls.lock().unwrap() == vec!["layout", "log_a", "log_b", "repaint"]);
+    }
+
+    #[test]
+    fn once_listener() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+
+        let cc2 = change_count.clone();
+        tracked_data.add_once_listener(0,
+                                       move |_: &i32, _: &i32| {
+                                           let data = &mut *cc2.lock().unwrap();
+                                           *data += 1;
+                                       });
+        tracked_data.add_listener(1, |_: &i32, _: &i32| {});
+
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(*change_count.lock().unwrap() == 1);
+        assert!(!tracked_data.contains_listener(&0));
+        assert!(tracked_data.contains_listener(&1));
+
+        // A once listener which never fired can still be removed.
+        tracked_data.add_once_listener(2, |_: &i32, _: &i32| {});
+        assert!(tracked_data.remove_listener(&2).is_some());
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn changes() {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        let mut tracked_data = DataTracker::new(1);
+        let mut changes = tracked_data.changes(0);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(Pin::new(&mut changes.next()).poll(&mut cx) == Poll::Pending);
+
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(Pin::new(&mut changes.next()).poll(&mut cx) == Poll::Ready(Some((1, 2))));
+        assert!(Pin::new(&mut changes.next()).poll(&mut cx) == Poll::Ready(Some((2, 3))));
+        assert!(Pin::new(&mut changes.next()).poll(&mut cx) == Poll::Pending);
+
+        tracked_data.set(4);
+        assert!(changes.poll_change(&mut cx) == Poll::Ready(Some((3, 4))));
+
+        drop(tracked_data);
+        assert!(Pin::new(&mut changes.next()).poll(&mut cx) == Poll::Ready(None));
+        assert!(changes.poll_change(&mut cx) == Poll::Ready(None));
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn add_sender() {
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_sender(0, tx);
+
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(rx.try_recv().unwrap() == (1, 2));
+        assert!(rx.try_recv().unwrap() == (2, 3));
+        assert!(rx.try_recv().is_err());
+
+        // A dropped receiver is ignored.
+        drop(rx);
+        tracked_data.set(4);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn add_bounded_sender() {
+        use super::DropPolicy;
+
+        let mut tracked_data = DataTracker::new(0);
+        let oldest = tracked_data.add_bounded_sender(0, 2, DropPolicy::Oldest);
+        let newest = tracked_data.add_bounded_sender(1, 2, DropPolicy::Newest);
+        for i in 1..5 {
+            tracked_data.set(i);
+        }
+        assert!(oldest.dropped_count() == 2 && newest.dropped_count() == 2);
+        assert!(oldest.try_recv() == Some((2, 3)) && oldest.try_recv() == Some((3, 4)));
+        assert!(newest.try_recv() == Some((0, 1)) && newest.try_recv() == Some((1, 2)));
+        assert!(oldest.is_empty());
+
+        // No more changes arrive once the tracker is dropped.
+        tracked_data.set(5);
+        drop(tracked_data);
+        assert!(oldest.recv() == Some((4, 5)));
+        assert!(oldest.recv().is_none());
+    }
+
+    #[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+    #[test]
+    fn debounced_listener() {
+        use std::time::Duration;
+
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_debounced_listener(0,
+                                            Duration::from_millis(50),
+                                            move |old_value: &i32, new_value: &i32| {
+                                                tx.send((*old_value, *new_value)).unwrap();
+                                            });
+
+        tracked_data.set(2);
+        tracked_data.set(3);
+        tracked_data.set(4);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == (1, 4));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // Dropping the tracker cancels the pending call.
+        tracked_data.set(5);
+        drop(tracked_data);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+    #[test]
+    fn rate_limited_listener() {
+        use std::time::Duration;
+
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_rate_limited_listener(0,
+                                               Duration::from_millis(200),
+                                               move |old_value: &i32, new_value: &i32| {
+                                                   tx.send((*old_value, *new_value)).unwrap();
+                                               });
+
+        // The first change is delivered at once, the others on the trailing
+        // edge.
+        tracked_data.set(2);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == (1, 2));
+        tracked_data.set(3);
+        tracked_data.set(4);
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == (2, 4));
+    }
+
+    #[cfg(all(feature = "timer", not(any(feature = "no_send", feature = "no_std"))))]
+    #[test]
+    fn windowed_listener() {
+        use std::time::Duration;
+
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_windowed_listener(0,
+                                           Duration::from_millis(50),
+                                           move |changes: &[(i32, i32)]| {
+                                               tx.send(changes.to_vec()).unwrap();
+                                           });
+
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == vec![(1, 2), (2, 3)]);
+
+        // Dropping the tracker delivers the collected changes.
+        tracked_data.set(4);
+        drop(tracked_data);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == vec![(3, 4)]);
+    }
+
+    #[cfg(not(any(feature = "no_send", feature = "no_std")))]
+    #[test]
+    fn offloaded_listener() {
+        use std::time::Duration;
+        use super::WorkerPool;
+
+        let (tx, rx) = ::std::sync::mpsc::channel();
+        let (release_tx, release_rx) = ::std::sync::mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_offloaded_listener(0,
+                                            WorkerPool::new(1),
+                                            move |old_value: &i32, new_value: &i32| {
+                                                release_rx.lock().unwrap().recv().unwrap();
+                                                tx.send((*old_value, *new_value)).unwrap();
+                                            });
+
+        // Both changes are reported while the callback is still blocked.
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        release_tx.send(()).unwrap();
+        release_tx.send(()).unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == (1, 2));
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == (2, 3));
+    }
+
+    #[test]
+    fn batch() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(vec![1]);
+
+        let calls2 = calls.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &Vec<i32>, new_value: &Vec<i32>| {
+                                      calls2.lock().unwrap().push((old_value.clone(), new_value.clone()));
+                                  });
+
+        tracked_data.batch(|v| {
+            v.push(2);
+            v.push(3);
+            v.retain(|&x| x != 1);
+        });
+        assert!(*calls.lock().unwrap() == vec![(vec![1], vec![2, 3])]);
+
+        // No net change, no notification.
+        tracked_data.batch(|v| {
+            v.push(4);
+            v.pop();
+        });
+        assert!(calls.lock().unwrap().len() == 1);
+    }
+
+    #[test]
+    fn no_copy_without_listeners() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(PartialEq)]
+        struct CountClones;
+
+        impl Clone for CountClones {
+            fn clone(&self) -> CountClones {
+                CLONES.fetch_add(1, Ordering::SeqCst);
+                CountClones
+            }
+        }
+
+        let mut tracked_data = DataTracker::new(CountClones);
+
+        drop(tracked_data.as_tracked_mut());
+        assert!(CLONES.load(Ordering::SeqCst) == 0);
+
+        tracked_data.add_listener(0, |_: &CountClones, _: &CountClones| {});
+        drop(tracked_data.as_tracked_mut());
+        assert!(CLONES.load(Ordering::SeqCst) == 1);
+    }
+
+    #[test]
+    fn add_fn() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+
+        let calls2 = calls.clone();
+        tracked_data.add_fn(0, move |old_value, new_value| {
+            calls2.lock().unwrap().push((*old_value, *new_value));
+        });
+
+        tracked_data.set(2);
+        assert!(*calls.lock().unwrap() == vec![(1, 2)]);
+    }
+
+    #[test]
+    fn journal() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::with_journal(0, 3);
+        assert!(tracked_data.recent_changes().is_empty());
+
+        // Changes are recorded without any listeners.
+        for i in 1..6 {
+            *tracked_data.as_tracked_mut() = i;
+        }
+        tracked_data.set(5);
+        tracked_data.set(6);
+        assert!(tracked_data.recent_changes() == &[(3, 4), (4, 5), (5, 6)][..]);
+
+        let mut untracked: DataTracker<i32, u8> = DataTracker::new(0);
+        untracked.set(1);
+        assert!(untracked.recent_changes().is_empty());
+    }
+
+    #[test]
+    fn replay() {
+        let mut recorded: DataTracker<i32, u8> = DataTracker::with_journal(0, 10);
+        for i in 1..4 {
+            recorded.set(i);
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut replayed = DataTracker::new(0);
+        let seen2 = seen.clone();
+        replayed.add_listener(0, move |old_value: &i32, new_value: &i32| {
+            seen2.lock().unwrap().push((*old_value, *new_value));
+        });
+        replayed.replay(recorded.recent_changes());
+        assert!(*seen.lock().unwrap() == recorded.recent_changes());
+
+        // Values equal to the current one are not reported.
+        replayed.replay(&[(0, 3)]);
+        assert!(seen.lock().unwrap().len() == 3);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn map() {
+        let mut tracked_data: DataTracker<(i32, i32), u8> = DataTracker::new((1, 2));
+        let first = tracked_data.map(0, |value: &(i32, i32)| value.0);
+        assert!(first.get() == 1);
+
+        let change_count = Arc::new(Mutex::new(0));
+        let cc2 = change_count.clone();
+        first.add_listener("count",
+                           move |_: &i32, _: &i32| *cc2.lock().unwrap() += 1);
+
+        tracked_data.set((1, 3));
+        assert!(*change_count.lock().unwrap() == 0);
+        tracked_data.set((4, 3));
+        assert!(first.get() == 4);
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn deref() {
+        #[derive(Clone, PartialEq)]
+        struct MyData {
+            a: u8,
+        }
+        let mut tracked_data: DataTracker<MyData, u8> = DataTracker::new(MyData { a: 1 });
+        assert!(tracked_data.a == 1);
+        tracked_data.as_tracked_mut().a = 2;
+        assert!(tracked_data.a == 2);
+    }
+
+    #[test]
+    fn get_cloned() {
+        let mut tracked_data: DataTracker<Vec<u8>, u8> = DataTracker::new(vec![1]);
+        let copy = tracked_data.get_cloned();
+        tracked_data.as_tracked_mut().push(2);
+        assert!(copy == vec![1]);
+        assert!(tracked_data.get_cloned() == vec![1, 2]);
+    }
+
+    #[test]
+    fn filtered_listener() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let predicate_calls = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(20);
+
+        let seen2 = seen.clone();
+        let pc2 = predicate_calls.clone();
+        tracked_data.add_filtered_listener(0,
+                                           move |old_value: &i32, new_value: &i32| {
+                                               *pc2.lock().unwrap() += 1;
+                                               *old_value <= 30 && *new_value > 30
+                                           },
+                                           move |_: &i32, new_value: &i32| {
+                                               seen2.lock().unwrap().push(*new_value);
+                                           });
+
+        tracked_data.set(25);
+        tracked_data.set(35);
+        tracked_data.set(40);
+        assert!(*seen.lock().unwrap() == vec![35]);
+        assert!(*predicate_calls.lock().unwrap() == 3);
+
+        // The predicate is not called for unchanged values.
+        tracked_data.notify_all();
+        assert!(*predicate_calls.lock().unwrap() == 3);
+    }
+
+    #[test]
+    fn filtered_listener_with_comparator() {
+        let mut tracked_data = DataTracker::with_comparator(1.0, |a: &f64, b: &f64| {
+            (a - b).abs() < 0.5
+        });
+        tracked_data.add_filtered_listener(0,
+                                           |_: &f64, _: &f64| panic!("equal values"),
+                                           |_: &f64, _: &f64| {});
+        // The values are equal according to the comparator, though not
+        // identical.
+        let filtered = tracked_data.peek_listener(&0).unwrap();
+        filtered.on_changed(&1.0, &1.25);
+    }
+
+    #[test]
+    fn transition_listener() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        enum State {
+            Idle,
+            Running,
+            Done,
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(State::Idle);
+        let transitions = [(0, State::Idle, State::Running), (1, State::Running, State::Done)];
+        for &(key, from, to) in transitions.iter() {
+            let seen2 = seen.clone();
+            tracked_data.add_transition_listener(key, from, to, move |_: &State, _: &State| {
+                seen2.lock().unwrap().push(key);
+            });
+        }
+
+        tracked_data.set(State::Done);
+        tracked_data.set(State::Idle);
+        tracked_data.set(State::Running);
+        tracked_data.set(State::Done);
+        assert!(*seen.lock().unwrap() == vec![0, 1]);
+    }
+
+    #[test]
+    fn weak_listener() {
+        let component = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(0);
+        tracked_data.add_weak_listener(0,
+                                       &component,
+                                       |seen: &Mutex<Vec<i32>>, _: &i32, new_value: &i32| {
+                                           seen.lock().unwrap().push(*new_value);
+                                       });
+
+        tracked_data.set(1);
+        assert!(*component.lock().unwrap() == vec![1]);
+
+        // The tracker does not keep the component alive.
+        let weak = Arc::downgrade(&component);
+        drop(component);
+        assert!(weak.upgrade().is_none());
+
+        tracked_data.set(2);
+        assert!(!tracked_data.contains_listener(&0));
+    }
+
+    #[test]
+    fn peek_listener() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        {
+            let callback = tracked_data.peek_listener(&0).unwrap();
+            callback.on_changed(&1, &2);
+        }
+        assert!(tracked_data.peek_listener(&1).is_none());
+        assert!(tracked_data.listener_count() == 1);
+    }
+
+    #[test]
+    fn replace_listener() {
+        use super::ListenerSlot;
+
+        let mut tracked_data = DataTracker::new(1);
+        match tracked_data.replace_listener(0, |_: &i32, _: &i32| {}) {
+            ListenerSlot::Inserted => {}
+            ListenerSlot::Replaced(_) => panic!("nothing to replace"),
+        }
+        match tracked_data.replace_listener(0, |_: &i32, _: &i32| {}) {
+            ListenerSlot::Inserted => panic!("expected a replacement"),
+            ListenerSlot::Replaced(_) => {}
+        }
+        assert!(tracked_data.listener_count() == 1);
+    }
+
+    #[test]
+    fn new_with_listeners() {
+        use super::{Map, OnChanged};
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut listeners: Map<&str, Box<dyn OnChanged<i32>>> = Map::new();
+        for key in ["a", "b"].iter() {
+            let cc2 = change_count.clone();
+            listeners.insert(key, Box::new(move |_: &i32, _: &i32| *cc2.lock().unwrap() += 1));
+        }
+        let mut tracked_data = DataTracker::new_with_listeners(1, listeners);
+        assert!(tracked_data.listener_count() == 2);
+        tracked_data.set(2);
+        assert!(*change_count.lock().unwrap() == 2);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn panicking_listener() {
+        use std::panic;
+        use super::ListenerPanic;
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| panic!("bad listener"));
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(1, move |_: &i32, _: &i32| *cc2.lock().unwrap() += 1);
+
+        // The panic is resumed after the remaining listeners ran.
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| tracked_data.set(2)));
+        assert!(result.is_err());
+        assert!(*change_count.lock().unwrap() == 1);
+
+        // try_commit() reports the panic as an error.
+        let mut x = tracked_data.as_tracked_mut();
+        *x = 3;
+        let errors = x.try_commit().unwrap_err();
+        assert!(errors.len() == 1);
+        assert!(errors[0].downcast_ref::<ListenerPanic>().unwrap().message() == "bad listener");
+        assert!(*change_count.lock().unwrap() == 2);
+
+        // A listener panicking while the modifier is dropped during unwinding
+        // does not abort the process.
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 4;
+            panic!("bad modification");
+        }));
+        assert!(result.is_err());
+        assert!(*change_count.lock().unwrap() == 3);
+    }
+
+    #[test]
+    fn nested_modifier() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 2;
+            {
+                let mut y = x.nested();
+                *y = 3;
+                assert!(!y.changed());
+            }
+            assert!(seen.lock().unwrap().is_empty());
+            let mut y = x.nested();
+            *y = 4;
+            assert!(!y.commit());
+            assert!(x.changed());
+        }
+        assert!(*seen.lock().unwrap() == vec![(1, 4)]);
+    }
+
+    #[test]
+    fn with_listener() {
+        let change_count = Arc::new(Mutex::new(0));
+        let cc2 = change_count.clone();
+        let mut tracked_data = DataTracker::new(1)
+            .with_listener(0, |_: &i32, _: &i32| {})
+            .with_listener(1, move |_: &i32, _: &i32| *cc2.lock().unwrap() += 1);
+        tracked_data.set(2);
+        assert!(tracked_data.listener_keys().cloned().collect::<Vec<_>>() == vec![0, 1]);
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn notifications_enabled() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        tracked_data.set_notifications_enabled(false);
+        assert!(!tracked_data.notifications_enabled());
+        tracked_data.set(2);
+        *tracked_data.as_tracked_mut() = 3;
+        tracked_data.notify_all();
+        assert!(seen.lock().unwrap().is_empty());
+
+        // One catch-up notification for the net change.
+        tracked_data.set_notifications_enabled(true);
+        assert!(*seen.lock().unwrap() == vec![(1, 3)]);
+
+        // No catch-up if the data ends up unchanged.
+        tracked_data.set_notifications_enabled(false);
+        tracked_data.set(4);
+        tracked_data.set(3);
+        tracked_data.set_notifications_enabled(true);
+        assert!(seen.lock().unwrap().len() == 1);
+    }
+
+    #[test]
+    fn id_listener() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut a = DataTracker::new_with_id(1, "a");
+        let mut b = DataTracker::new_with_id(1, "b");
+        assert!(*a.id() == "a");
+        for tracker in [&mut a, &mut b].iter_mut() {
+            let seen2 = seen.clone();
+            tracker.add_id_listener(0, move |id: &&str, _: &i32, new_value: &i32| {
+                seen2.lock().unwrap().push((*id, *new_value));
+            });
+        }
+        a.set(2);
+        b.set(3);
+        assert!(*seen.lock().unwrap() == vec![("a", 2), ("b", 3)]);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn coalescing_listener() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        let callback = move |old_value: &i32, new_value: &i32| {
+            seen2.lock().unwrap().push((*old_value, *new_value));
+        };
+        let coalescer = tracked_data.add_coalescing_listener(0, callback);
+
+        assert!(!coalescer.flush());
+        tracked_data.set(2);
+        tracked_data.set(3);
+        tracked_data.set(4);
+        assert!(coalescer.has_pending());
+        assert!(seen.lock().unwrap().is_empty());
+        assert!(coalescer.flush());
+        assert!(*seen.lock().unwrap() == vec![(1, 4)]);
+
+        // Changes which cancel each other out are not delivered.
+        tracked_data.set(5);
+        tracked_data.set(4);
+        assert!(!coalescer.flush());
+        assert!(!coalescer.has_pending());
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn coalescing_listener_with_comparator() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data: DataTracker<f64, u8> =
+            DataTracker::with_comparator(1.0, |a: &f64, b: &f64| (a - b).abs() < 0.5);
+        let seen2 = seen.clone();
+        let callback = move |old_value: &f64, new_value: &f64| {
+            seen2.lock().unwrap().push((*old_value, *new_value));
+        };
+        let coalescer = tracked_data.add_coalescing_listener(0, callback);
+
+        // Each step is a change, but the net change is within the tolerance.
+        tracked_data.set(2.0);
+        tracked_data.set(1.25);
+        assert!(coalescer.has_pending());
+        assert!(!coalescer.flush());
+        tracked_data.set(3.0);
+        assert!(coalescer.flush());
+        assert!(*seen.lock().unwrap() == vec![(1.25, 3.0)]);
+    }
+
+    #[test]
+    fn remove_listener_by_borrowed_key() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener("a".to_string(), |_: &i32, _: &i32| {});
+
+        assert!(tracked_data.contains_listener("a"));
+        assert!(tracked_data.remove_listener("a").is_some());
+        assert!(!tracked_data.contains_listener("a"));
+    }
+
+    #[test]
+    fn set_listeners() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+
+        let cc2 = change_count.clone();
+        let mut listeners: super::Map<u8, Box<dyn super::OnChanged<i32>>> = super::Map::new();
+        listeners.insert(1,
+                         Box::new(move |_: &i32, _: &i32| {
+                             *cc2.lock().unwrap() += 1;
+                         }));
+        let old = tracked_data.set_listeners(listeners);
+        assert!(old.len() == 1 && old.contains_key(&0));
+        assert!(*change_count.lock().unwrap() == 0);
+        assert!(!tracked_data.contains_listener(&0));
+
+        tracked_data.set(2);
+        assert!(*change_count.lock().unwrap() == 1);
+
+        // Restore the previous callbacks.
+        let swapped = tracked_data.set_listeners(old);
+        assert!(swapped.contains_key(&1));
+        assert!(tracked_data.contains_listener(&0));
+        tracked_data.set(3);
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn swapped_listeners_keep_registration() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push("late");
+        tracked_data.add_listener_with_priority("late", 5, callback);
+        let seen2 = seen.clone();
+        let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push("once");
+        tracked_data.add_once_listener("once", callback);
+
+        let none: super::Map<&str, super::Registration<i32>> = super::Map::new();
+        let old = tracked_data.set_listeners(none);
+        assert!(old["late"].priority() == 5 && old["once"].is_once());
+        tracked_data.set_listeners(old);
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() == vec!["once", "late", "late"]);
+    }
+
+    #[test]
+    fn always_notify() {
+        // PartialEq is not required.
+        #[derive(Clone)]
+        struct Reading {
+            value: f64,
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new_always_notify(Reading { value: 1.0 });
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |_: &Reading, _: &Reading| {
+                                      *cc2.lock().unwrap() += 1;
+                                  });
+
+        tracked_data.as_tracked_mut().value = 2.0;
+        // Not modified, but the listener is called anyway.
+        assert!(tracked_data.as_tracked_mut().commit());
+        tracked_data.set(Reading { value: 2.0 });
+        assert!(*change_count.lock().unwrap() == 3);
+        assert!(tracked_data.as_ref().value == 2.0);
+    }
+
+    #[test]
+    fn with_comparator() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::with_comparator(1.0,
+                                                            |a: &f64, b: &f64| (a - b).abs() < 0.01);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &f64, new_value: &f64| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        *tracked_data.as_tracked_mut() += 0.001;
+        tracked_data.set(2.0);
+        {
+            let x = tracked_data.as_tracked_mut();
+            assert!(!x.changed());
+        }
+        assert!(*seen.lock().unwrap() == vec![(1.001, 2.0)]);
+    }
+
+    #[test]
+    fn fire_count() {
+        let mut tracked_data = DataTracker::with_fire_counts(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        tracked_data.set(2);
+        tracked_data.set(3);
+        tracked_data.set(3);
+        tracked_data.add_listener(1, |_: &i32, _: &i32| {});
+        tracked_data.notify_all();
+
+        assert!(tracked_data.fire_count(&0) == Some(3));
+        assert!(tracked_data.fire_count(&1) == Some(1));
+        assert!(tracked_data.fire_count(&2).is_none());
+
+        // Not counted by default.
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        tracked_data.set(2);
+        assert!(tracked_data.fire_count(&0).is_none());
+    }
+
+    #[test]
+    fn get_mut_unchecked() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(vec![0; 3]);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0, move |old_value: &Vec<i32>, new_value: &Vec<i32>| {
+            seen2.lock().unwrap().push((old_value.clone(), new_value.clone()));
+        });
+
+        for i in 0..3 {
+            tracked_data.get_mut_unchecked()[i] = 1;
+        }
+        assert!(seen.lock().unwrap().is_empty());
+        tracked_data.notify_all();
+        assert!(*seen.lock().unwrap() == vec![(vec![1; 3], vec![1; 3])]);
+    }
+
+    #[test]
+    fn as_mut_does_not_notify() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |_: &i32, _: &i32| {
+                                      *cc2.lock().unwrap() += 1;
+                                  });
+
+        *tracked_data.as_mut() = 2;
+        assert!(*tracked_data.as_ref() == 2);
+        assert!(*change_count.lock().unwrap() == 0);
+    }
+
+    #[test]
+    fn version() {
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        assert!(tracked_data.version() == 0);
+
+        tracked_data.set(2);
+        tracked_data.set(2);
+        assert!(tracked_data.version() == 1);
+        {
+            // A reverted modification is not a change.
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 3;
+            *x = 2;
         }
+        assert!(tracked_data.version() == 1);
+        *tracked_data.as_tracked_mut() = 3;
+        tracked_data.notify_all();
+        assert!(tracked_data.version() == 2);
+    }
 
-        assert!(*did_run.lock().unwrap() == true);
+    #[test]
+    fn version_without_observers() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        // Without a copy, any Modifier scope counts as a change.
+        *tracked_data.as_tracked_mut() = 2;
+        assert!(tracked_data.version() == 1);
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x.nested() = 3;
+        }
+        assert!(tracked_data.version() == 2);
+        tracked_data.as_tracked_mut().mark_clean();
+        assert!(tracked_data.version() == 2);
+        tracked_data.set(3);
+        assert!(tracked_data.version() == 2);
+        tracked_data.set(4);
+        assert!(tracked_data.version() == 3);
+
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        tracked_data.set_notifications_enabled(false);
+        tracked_data.set(5);
+        *tracked_data.as_tracked_mut() = 6;
+        assert!(tracked_data.version() == 5);
+        tracked_data.set_notifications_enabled(true);
+        assert!(tracked_data.version() == 6);
+    }
+
+    #[test]
+    fn dirty_hints() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |_: &i32, _: &i32| {
+                                      *cc2.lock().unwrap() += 1;
+                                  });
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 2;
+            x.mark_clean();
+            assert!(!x.changed());
+        }
+        assert!(*change_count.lock().unwrap() == 0);
+
+        let mut x = tracked_data.as_tracked_mut();
+        x.mark_dirty();
+        assert!(x.commit());
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn drain_listeners() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0,
+                                  move |_: &i32, _: &i32| {
+                                      *cc2.lock().unwrap() += 1;
+                                  });
+
+        let listeners = tracked_data.drain_listeners();
+        assert!(listeners.len() == 1);
+        assert!(!tracked_data.has_listeners());
+        tracked_data.set(2);
+        assert!(*change_count.lock().unwrap() == 0);
+
+        let mut other = DataTracker::new_with_listeners(10, listeners);
+        other.set(11);
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn drained_listeners_keep_registration() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        for &(key, priority) in [("late", 5), ("early", -5)].iter() {
+            let seen2 = seen.clone();
+            let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push(key);
+            tracked_data.add_listener_with_priority(key, priority, callback);
+        }
+        let seen2 = seen.clone();
+        let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push("once");
+        tracked_data.add_once_listener("once", callback);
+        tracked_data.add_listener_in_group(7, "grouped", |_: &i32, _: &i32| {});
+
+        let listeners = tracked_data.drain_listeners();
+        assert!(listeners["early"].priority() == -5 && listeners["once"].is_once());
+        assert!(listeners["grouped"].group() == Some(7));
+        let mut other = DataTracker::new_with_listeners(10, listeners);
+        other.set(11);
+        other.set(12);
+        assert!(*seen.lock().unwrap() == vec!["early", "once", "late", "early", "late"]);
+        assert!(other.remove_group(7) == 1);
+    }
+
+    #[test]
+    fn map_key() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        for &key in [1u8, 2, 12].iter() {
+            let seen2 = seen.clone();
+            tracked_data.add_listener(key, move |_: &i32, _: &i32| {
+                seen2.lock().unwrap().push(key);
+            });
+        }
+
+        // 2 and 12 collide, so only the one added last remains.
+        let mut tracked_data = tracked_data.map_key(|key| format!("key{}", key % 10));
+        assert!(tracked_data.listener_count() == 2 && tracked_data.contains_listener("key2"));
+        tracked_data.set(2);
+        assert!(*seen.lock().unwrap() == vec![1, 12]);
+    }
+
+    #[test]
+    fn merge_listeners_from() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let mut other = DataTracker::new(1);
+        for &(name, key) in [("self a", "a"), ("other a", "a"), ("other b", "b")].iter() {
+            let seen2 = seen.clone();
+            let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push(name);
+            if name.starts_with("self") {
+                tracked_data.add_listener(key, callback);
+            } else {
+                other.add_listener(key, callback);
+            }
+        }
+
+        let displaced = tracked_data.merge_listeners_from(&mut other);
+        assert!(displaced.len() == 1 && displaced.contains_key("a"));
+        assert!(!other.has_listeners());
+        assert!(seen.lock().unwrap().is_empty());
+
+        tracked_data.set(2);
+        other.set(2);
+        assert!(*seen.lock().unwrap() == vec!["self a", "other b"]);
+    }
+
+    #[test]
+    fn merged_listeners_keep_registration() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let mut other = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.add_listener("self", move |_: &i32, _: &i32| {
+            seen2.lock().unwrap().push("self");
+        });
+        let seen2 = seen.clone();
+        let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push("early");
+        other.add_listener_with_priority("early", -1, callback);
+        let seen2 = seen.clone();
+        let callback = move |_: &i32, _: &i32| seen2.lock().unwrap().push("once");
+        other.add_once_listener("once", callback);
+
+        assert!(tracked_data.merge_listeners_from(&mut other).is_empty());
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() == vec!["early", "self", "once", "early", "self"]);
+    }
+
+    #[cfg(not(feature = "no_std"))]
+    #[test]
+    fn pipe_to() {
+        let mut source = DataTracker::new(1);
+        let mirror: super::SharedTracker<i32, u8> = super::SharedTracker::new(0);
+        source.pipe_to(0, mirror.clone());
+        assert!(mirror.get() == 1);
+
+        // Bind the mirror and a second tracker to each other.
+        let other: super::SharedTracker<i32, u8> = super::SharedTracker::new(0);
+        let (mirror2, other2) = (mirror.clone(), other.clone());
+        mirror.add_listener(0, move |_: &i32, new_value: &i32| other2.set(*new_value));
+        other.add_listener(0, move |_: &i32, new_value: &i32| mirror2.set(*new_value));
+
+        source.set(2);
+        assert!(mirror.get() == 2 && other.get() == 2);
+        source.remove_listener(&0);
+        source.set(3);
+        assert!(mirror.get() == 2);
+    }
+
+    #[test]
+    fn modifier_original() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        {
+            // Nothing to compare against without listeners.
+            assert!(tracked_data.as_tracked_mut().original().is_none());
+        }
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 2;
+            *x = 3;
+            assert!(x.original() == Some(&1));
+        }
+        assert!(*seen.lock().unwrap() == vec![(1, 3)]);
+    }
+
+    #[test]
+    fn normalizer() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(5);
+        tracked_data.set_normalizer(|value: &mut i32| if *value > 10 {
+            *value = 10;
+        });
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        *tracked_data.as_tracked_mut() = 20;
+        assert!(*tracked_data.as_ref() == 10);
+        // Normalized back to the original value, so no change.
+        tracked_data.set(30);
+        {
+            let mut x = tracked_data.as_tracked_mut();
+            *x = 4;
+            // A nested scope leaves normalizing to the outer one.
+            *x.nested() = 50;
+        }
+        *tracked_data.as_tracked_mut() = 7;
+        assert!(*seen.lock().unwrap() == vec![(5, 10), (10, 7)]);
+    }
+
+    #[test]
+    fn replace_with() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(2);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        assert!(tracked_data.replace_with(|value| value * 10) == 2);
+        assert!(tracked_data.replace_with(|value| *value) == 20);
+        assert!(*seen.lock().unwrap() == vec![(2, 20)]);
+    }
+
+    #[test]
+    fn take() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(vec![1, 2]);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0, move |old_value: &Vec<i32>, new_value: &Vec<i32>| {
+            seen2.lock().unwrap().push((old_value.clone(), new_value.clone()));
+        });
+
+        assert!(tracked_data.take() == vec![1, 2]);
+        assert!(tracked_data.take().is_empty());
+        assert!(*seen.lock().unwrap() == vec![(vec![1, 2], vec![])]);
+    }
+
+    #[test]
+    fn replace_if_changed() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(2);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &i32, new_value: &i32| {
+                                      seen2.lock().unwrap().push((*old_value, *new_value));
+                                  });
+
+        assert!(tracked_data.replace_if_changed(3));
+        assert!(!tracked_data.replace_if_changed(3));
+        assert!(*seen.lock().unwrap() == vec![(2, 3)]);
+    }
+
+    #[test]
+    fn replace_if_changed_compares_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let comparisons = Arc::new(AtomicUsize::new(0));
+        let comparisons2 = comparisons.clone();
+        let mut tracked_data: DataTracker<i32, u8> =
+            DataTracker::with_comparator(1, move |a: &i32, b: &i32| {
+                comparisons2.fetch_add(1, Ordering::SeqCst);
+                a == b
+            });
+        assert!(tracked_data.replace_if_changed(2));
+        assert!(comparisons.load(Ordering::SeqCst) == 1);
+    }
+
+    #[test]
+    fn add_listener_and_fire() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.add_listener_and_fire(0,
+                                           move |old_value: &i32, new_value: &i32| {
+                                               seen2.lock().unwrap().push((*old_value, *new_value));
+                                           });
+        tracked_data.set(2);
+        assert!(*seen.lock().unwrap() == vec![(1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn disable_listener() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(1);
+        let cc2 = change_count.clone();
+        tracked_data.add_once_listener(0,
+                                       move |_: &i32, _: &i32| {
+                                           *cc2.lock().unwrap() += 1;
+                                       });
+
+        assert!(tracked_data.disable_listener(&0));
+        assert!(!tracked_data.disable_listener(&1));
+        tracked_data.set(2);
+        assert!(*change_count.lock().unwrap() == 0);
+        assert!(tracked_data.contains_listener(&0));
+
+        assert!(tracked_data.enable_listener(&0));
+        tracked_data.set(3);
+        assert!(*change_count.lock().unwrap() == 1);
+        assert!(!tracked_data.contains_listener(&0));
+    }
+
+    #[test]
+    fn add_listener_if_absent() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        for i in 0..2 {
+            let seen2 = seen.clone();
+            let inserted = tracked_data.add_listener_if_absent(0, move |_: &i32, _: &i32| {
+                seen2.lock().unwrap().push(i);
+            });
+            assert!(inserted == (i == 0));
+        }
+        tracked_data.set(2);
+        assert!(*seen.lock().unwrap() == vec![0]);
+    }
+
+    #[test]
+    fn default_listener() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.set_default_listener(move |_: &i32, new_value: &i32| {
+            seen2.lock().unwrap().push(("default", *new_value));
+        });
+
+        tracked_data.set(2);
+        let seen3 = seen.clone();
+        tracked_data.add_listener_with_priority(0,
+                                                10,
+                                                move |_: &i32, new_value: &i32| {
+                                                    let change = ("keyed", *new_value);
+                                                    seen3.lock().unwrap().push(change);
+                                                });
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() == vec![("default", 2), ("keyed", 3), ("default", 3)]);
+
+        tracked_data.notify_all();
+        assert!(seen.lock().unwrap().len() == 5);
+        assert!(seen.lock().unwrap()[4] == ("default", 3));
+
+        assert!(tracked_data.take_default_listener().is_some());
+        tracked_data.set(4);
+        assert!(seen.lock().unwrap().len() == 6);
+    }
+
+    #[test]
+    fn listener_groups() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        for &(group, key) in [(1, "a"), (1, "b"), (2, "c")].iter() {
+            let seen2 = seen.clone();
+            tracked_data.add_listener_in_group(group, key, move |_: &i32, _: &i32| {
+                seen2.lock().unwrap().push(key);
+            });
+        }
+        let seen2 = seen.clone();
+        tracked_data.add_listener("d", move |_: &i32, _: &i32| seen2.lock().unwrap().push("d"));
+
+        assert!(tracked_data.remove_group(1) == 2);
+        assert!(tracked_data.remove_group(1) == 0);
+        tracked_data.set(2);
+        assert!(*seen.lock().unwrap() == vec!["c", "d"]);
+    }
+
+    #[test]
+    fn add_listener_strict() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        for i in 0..2 {
+            let seen2 = seen.clone();
+            let result = tracked_data.add_listener_strict("key", move |_: &i32, _: &i32| {
+                seen2.lock().unwrap().push(i);
+            });
+            if i == 0 {
+                assert!(result.is_ok());
+            } else {
+                assert!(result.unwrap_err().into_key() == "key");
+            }
+        }
+        tracked_data.set(2);
+        assert!(*seen.lock().unwrap() == vec![0]);
+    }
+
+    #[test]
+    fn change_detect() {
+        use super::ChangeDetect;
+
+        #[derive(Clone)]
+        struct Entry {
+            value: i32,
+            last_accessed: u64,
+        }
+
+        impl ChangeDetect for Entry {
+            fn is_changed(old: &Entry, new: &Entry) -> bool {
+                old.value != new.value
+            }
+        }
+
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(Entry {
+                                                    value: 1,
+                                                    last_accessed: 0,
+                                                });
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0, move |_: &Entry, _: &Entry| {
+            *cc2.lock().unwrap() += 1;
+        });
+
+        tracked_data.as_tracked_mut().last_accessed = 1;
+        assert!(*change_count.lock().unwrap() == 0);
+        tracked_data.as_tracked_mut().value = 2;
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn clone_without_listeners() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(vec![1]);
+        let cc2 = change_count.clone();
+        tracked_data.add_listener(0, move |_: &Vec<i32>, _: &Vec<i32>| {
+            *cc2.lock().unwrap() += 1;
+        });
+
+        let mut fork = tracked_data.clone();
+        assert!(!fork.has_listeners() && *fork.as_ref() == vec![1]);
+        fork.set(vec![2]);
+        assert!(*change_count.lock().unwrap() == 0);
+        assert!(*tracked_data.as_ref() == vec![1]);
+        tracked_data.set(vec![3]);
+        assert!(*change_count.lock().unwrap() == 1);
+    }
+
+    #[test]
+    fn change_and_noop_counts() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        *tracked_data.as_tracked_mut() = 2;
+        for _ in 0..2 {
+            let _x = tracked_data.as_tracked_mut();
+        }
+        tracked_data.set(3);
+        assert!(tracked_data.change_count() == 1 && tracked_data.noop_count() == 2);
+
+        tracked_data.reset_modifier_counts();
+        assert!(tracked_data.change_count() == 0 && tracked_data.noop_count() == 0);
+    }
+
+    #[test]
+    fn compare_and_set() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        tracked_data.add_listener(0, |_: &i32, _: &i32| {});
+        let version = tracked_data.version();
+        assert!(tracked_data.compare_and_set(version, 2).is_ok());
+        let err = tracked_data.compare_and_set(version, 3).unwrap_err();
+        assert!(err.actual_version() == version + 1);
+        assert!(*tracked_data.as_ref() == 2);
+    }
+
+    #[test]
+    fn compare_and_set_after_unobserved_write() {
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        let version = tracked_data.version();
+        // No copy is made without listeners, but the write still counts.
+        *tracked_data.as_tracked_mut() = 2;
+        assert!(tracked_data.compare_and_set(version, 3).is_err());
+        assert!(*tracked_data.as_ref() == 2);
+    }
+
+    #[test]
+    fn as_tracked_mut_with_snapshot() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(vec![1]);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &Vec<i32>, new_value: &Vec<i32>| {
+                                      seen2.lock().unwrap().push((old_value.clone(),
+                                                                  new_value.clone()));
+                                  });
+
+        let copy = tracked_data.get_cloned();
+        tracked_data.as_tracked_mut_with_snapshot(copy).push(2);
+        assert!(*seen.lock().unwrap() == vec![(vec![1], vec![1, 2])]);
+    }
+
+    #[test]
+    fn boxed_callback_by_value() {
+        use super::{OnChanged, TrackerCell};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen2 = seen.clone();
+        let boxed: Box<dyn OnChanged<i32>> = Box::new(move |_: &i32, new_value: &i32| {
+            seen2.lock().unwrap().push(*new_value);
+        });
+        let mut tracked_data = DataTracker::new(1);
+        tracked_data.add_listener(0, boxed);
+        tracked_data.set(2);
+
+        let seen3 = seen.clone();
+        let cell = TrackerCell::new(2);
+        cell.add_listener(0, move |_: &i32, new_value: &i32| {
+            seen3.lock().unwrap().push(-*new_value);
+        });
+        cell.set(3);
+        assert!(*seen.lock().unwrap() == vec![2, -3]);
+    }
+
+    // Test that DataTracker and SharedTracker implement Sync, at least
+    // if the owned data type T and the key type K implement Sync.
+    #[cfg(not(feature = "no_send"))]
+    #[test]
+    fn track_sync_impl() {
+        fn assert_sync<S: Sync>() {}
+
+        assert_sync::<DataTracker<i32, u8>>();
+        assert_sync::<DataTracker<Vec<String>, String, u32>>();
+        #[cfg(not(feature = "no_std"))]
+        assert_sync::<super::SharedTracker<i32, u8>>();
+    }
+
+    // Test that a callback which is Send but not Sync can be registered.
+    #[cfg(not(feature = "no_send"))]
+    #[test]
+    fn non_sync_listener() {
+        use std::cell::Cell;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        let seen2 = seen.clone();
+        let calls = Cell::new(0);
+        tracked_data.add_listener(0, move |_: &i32, new_value: &i32| {
+            calls.set(calls.get() + 1);
+            seen2.lock().unwrap().push((calls.get(), *new_value));
+        });
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() == vec![(1, 2), (2, 3)]);
     }
 
     // Test that instances of DataTracker implement Send, at least if
     // the owned data type T implements Send.
     #[cfg(not(feature = "no_send"))]
     #[test]
+    #[allow(clippy::explicit_auto_deref)]
     fn track_send_impl() {
 
         #[derive(Clone, PartialEq)]
@@ -0,0 +1,116 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Listeners which run on an executor rather than inline.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use super::OnChanged;
+
+/// A unit of work submitted to an `Executor`.
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// Trait defining something which runs jobs, such as a thread pool.
+///
+/// This is implemented for `WorkerPool` and for closures taking a `Job`, so
+/// that an external pool can be used with e.g. `|job| rayon::spawn(job)`.
+pub trait Executor: Send + Sync {
+    fn execute(&self, job: Job);
+}
+
+impl<F> Executor for F
+    where F: Fn(Job) + Send + Sync
+{
+    fn execute(&self, job: Job) {
+        self(job)
+    }
+}
+
+/// A simple pool of worker threads running the submitted jobs.
+///
+/// Cloning a `WorkerPool` gives another handle to the same threads. The
+/// threads exit once all handles have been dropped and the remaining jobs
+/// have run. A job which panics does not stop its thread, so the later jobs
+/// still run.
+#[derive(Clone)]
+pub struct WorkerPool {
+    sender: mpsc::Sender<Job>,
+}
+
+impl WorkerPool {
+    /// Start a pool of `threads` worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    pub fn new(threads: usize) -> WorkerPool {
+        assert!(threads > 0, "a WorkerPool needs at least one thread");
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            thread::spawn(move || loop {
+                let job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            });
+        }
+        WorkerPool { sender }
+    }
+}
+
+impl Executor for WorkerPool {
+    fn execute(&self, job: Job) {
+        // The workers only stop once every sender is gone.
+        let _ = self.sender.send(job);
+    }
+}
+
+/// Passes every change on to a callback running on an executor.
+pub struct Offloaded<T, E> {
+    executor: E,
+    callback: Arc<dyn OnChanged<T> + Sync>,
+}
+
+impl<T, E> Offloaded<T, E> {
+    pub fn new(executor: E, callback: Arc<dyn OnChanged<T> + Sync>) -> Offloaded<T, E> {
+        Offloaded { executor, callback }
+    }
+}
+
+impl<T, E> OnChanged<T> for Offloaded<T, E>
+    where T: Clone + Send + 'static,
+          E: Executor
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let callback = self.callback.clone();
+        let old_value = old_value.clone();
+        let new_value = new_value.clone();
+        self.executor.execute(Box::new(move || callback.on_changed(&old_value, &new_value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use super::{Executor, WorkerPool};
+
+    #[test]
+    fn panicking_job() {
+        let pool = WorkerPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        pool.execute(Box::new(|| panic!("job failed")));
+        pool.execute(Box::new(move || tx.send(42).unwrap()));
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap() == 42);
+    }
+}
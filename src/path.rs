@@ -0,0 +1,225 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Listeners which watch a nested field given by a path.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::{DataTracker, ListenerKey, OnChanged};
+
+/// Trait for tree-like data whose nested values are found by a path, such as
+/// a parsed JSON document.
+///
+/// A type of another crate, such as `serde_json::Value`, cannot implement
+/// this trait directly. Wrap it in a newtype instead:
+///
+/// ```ignore
+/// use data_tracker::PathLookup;
+///
+/// #[derive(Clone, PartialEq)]
+/// #[repr(transparent)]
+/// struct Json(serde_json::Value);
+///
+/// impl PathLookup for Json {
+///     fn get_key(&self, key: &str) -> Option<&Json> {
+///         self.0.get(key).map(Json::from_ref)
+///     }
+///     fn get_index(&self, index: usize) -> Option<&Json> {
+///         self.0.get(index).map(Json::from_ref)
+///     }
+/// }
+///
+/// impl Json {
+///     fn from_ref(value: &serde_json::Value) -> &Json {
+///         // Sound because `Json` is a `#[repr(transparent)]` newtype.
+///         unsafe { &*(value as *const serde_json::Value as *const Json) }
+///     }
+/// }
+/// ```
+pub trait PathLookup {
+    /// Return the member named `key` of an object.
+    fn get_key(&self, key: &str) -> Option<&Self>;
+    /// Return the element at `index` of an array.
+    fn get_index(&self, index: usize) -> Option<&Self>;
+}
+
+/// Error returned by
+/// [`DataTracker::add_path_listener()`](./struct.DataTracker.html#method.add_path_listener)
+/// when the path is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    path: String,
+    reason: &'static str,
+}
+
+impl PathError {
+    /// Return the malformed path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in path {:?}", self.reason, self.path)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Split a path such as `servers[0].port` into its segments.
+fn parse_path(path: &str) -> Result<Vec<Segment>, PathError> {
+    let error = |reason| {
+        PathError {
+            path: path.to_string(),
+            reason,
+        }
+    };
+    let mut segments = Vec::new();
+    if path.is_empty() {
+        return Ok(segments);
+    }
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(i) => part.split_at(i),
+            None => (part, ""),
+        };
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        } else if rest.is_empty() {
+            return Err(error("empty segment"));
+        }
+        while !rest.is_empty() {
+            let end = rest.find(']').ok_or_else(|| error("unclosed '['"))?;
+            let index = rest[1..end].parse().map_err(|_| error("invalid index"))?;
+            segments.push(Segment::Index(index));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return Err(error("unexpected characters after ']'"));
+            }
+        }
+    }
+    Ok(segments)
+}
+
+fn lookup<'a, T: PathLookup>(value: &'a T, segments: &[Segment]) -> Option<&'a T> {
+    let mut node = value;
+    for segment in segments {
+        node = match *segment {
+            Segment::Key(ref key) => node.get_key(key)?,
+            Segment::Index(index) => node.get_index(index)?,
+        };
+    }
+    Some(node)
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Add a callback which is only called when the value at `path` changes.
+    ///
+    /// The path consists of member names separated by `.`, each optionally
+    /// followed by array indices in brackets, as in `servers[0].port`. An
+    /// empty path denotes the whole data. The callback receives the whole
+    /// old and new data, and runs if the values at `path` differ, including
+    /// when the path exists in only one of them.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Ok(Some(original_callback))`. Otherwise, `Ok(None)` is
+    /// returned. If `path` is malformed, no callback is added and
+    /// `Err(PathError)` is returned.
+    pub fn add_path_listener<C>(&mut self,
+                                key: K,
+                                path: &str,
+                                callback: C)
+                                -> Result<Option<Box<dyn OnChanged<T>>>, PathError>
+        where T: PathLookup + PartialEq + 'static,
+              C: OnChanged<T> + 'static
+    {
+        let segments = parse_path(path)?;
+        let predicate = move |old_value: &T, new_value: &T| {
+            lookup(old_value, &segments) != lookup(new_value, &segments)
+        };
+        Ok(self.add_filtered_listener(key, predicate, callback))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::super::DataTracker;
+    use super::PathLookup;
+
+    #[derive(Clone, PartialEq)]
+    enum Json {
+        Num(i64),
+        Arr(Vec<Json>),
+        Obj(Vec<(String, Json)>),
+    }
+
+    impl PathLookup for Json {
+        fn get_key(&self, key: &str) -> Option<&Json> {
+            match *self {
+                Json::Obj(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1),
+                _ => None,
+            }
+        }
+        fn get_index(&self, index: usize) -> Option<&Json> {
+            match *self {
+                Json::Arr(ref elements) => elements.get(index),
+                _ => None,
+            }
+        }
+    }
+
+    fn config(port: i64, timeout: i64) -> Json {
+        let server = Json::Obj(vec![("port".to_string(), Json::Num(port))]);
+        Json::Obj(vec![("servers".to_string(), Json::Arr(vec![server])),
+                       ("timeout".to_string(), Json::Num(timeout))])
+    }
+
+    #[test]
+    fn path_listener() {
+        let change_count = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(config(80, 1));
+        let cc2 = change_count.clone();
+        let callback = move |_: &Json, _: &Json| *cc2.lock().unwrap() += 1;
+        let added = tracked_data.add_path_listener(0, "servers[0].port", callback);
+        assert!(added.unwrap().is_none());
+
+        tracked_data.set(config(80, 2));
+        assert!(*change_count.lock().unwrap() == 0);
+        tracked_data.set(config(8080, 2));
+        assert!(*change_count.lock().unwrap() == 1);
+        // The path disappears.
+        tracked_data.set(Json::Num(0));
+        assert!(*change_count.lock().unwrap() == 2);
+    }
+
+    #[test]
+    fn malformed_path() {
+        let mut tracked_data: DataTracker<Json, u8> = DataTracker::new(Json::Num(0));
+        for path in ["servers[0", "a..b", "servers[x]", "servers[0]x"].iter() {
+            let error = tracked_data.add_path_listener(0, path, |_: &Json, _: &Json| {})
+                .err()
+                .unwrap();
+            assert!(error.path() == *path);
+        }
+        let error = tracked_data.add_path_listener(0, "servers[0", |_: &Json, _: &Json| {});
+        assert!(error.err().unwrap().to_string() == "unclosed '[' in path \"servers[0\"");
+        assert!(!tracked_data.has_listeners());
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Listeners which can stop the remaining listeners from being called.
+
+use alloc::boxed::Box;
+use std::any::Any;
+
+use super::{sealed, DataTracker, ListenerError, ListenerKey, OnChanged};
+
+/// Whether the callbacks after a `HandlingOnChanged` callback are called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    /// Call the remaining callbacks.
+    Continue,
+    /// Do not call the remaining callbacks for this change.
+    Stop,
+}
+
+/// Trait defining a change notification callback which decides whether the
+/// remaining callbacks are called.
+#[cfg(not(feature = "no_send"))]
+pub trait HandlingOnChanged<T>: Send {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Propagation;
+}
+
+#[cfg(feature = "no_send")]
+pub trait HandlingOnChanged<T> {
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Propagation;
+}
+
+#[cfg(not(feature = "no_send"))]
+impl<F, T> HandlingOnChanged<T> for F
+    where F: Fn(&T, &T) -> Propagation + Send
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Propagation {
+        self(old_value, new_value)
+    }
+}
+
+#[cfg(feature = "no_send")]
+impl<F, T> HandlingOnChanged<T> for F
+    where F: Fn(&T, &T) -> Propagation
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) -> Propagation {
+        self(old_value, new_value)
+    }
+}
+
+/// Adapts a `HandlingOnChanged` callback so it can be stored with the others.
+struct Handling<H> {
+    handler: H,
+}
+
+impl<H, T> OnChanged<T> for Handling<H>
+    where H: HandlingOnChanged<T>
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        self.handler.on_changed(old_value, new_value);
+    }
+
+    fn handle_in_context(&self,
+                         _context: Option<&dyn Any>,
+                         old_value: &T,
+                         new_value: &T,
+                         _: sealed::Token)
+                         -> Result<Propagation, ListenerError> {
+        Ok(self.handler.on_changed(old_value, new_value))
+    }
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Add a callback which can stop the callbacks after it from being
+    /// called.
+    ///
+    /// Callbacks are called by ascending `priority` and then in the order in
+    /// which they were added, as with `add_listener_with_priority()`. If
+    /// `handler` returns `Propagation::Stop`, the remaining callbacks are not
+    /// called for this change. This allows a chain of handlers where the
+    /// first applicable one wins. The default listener (see
+    /// `set_default_listener()`) is still called.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_handling_listener<H>(&mut self,
+                                    key: K,
+                                    priority: i32,
+                                    handler: H)
+                                    -> Option<Box<dyn OnChanged<T>>>
+        where H: HandlingOnChanged<T> + 'static
+    {
+        self.inner.listeners.insert(key, priority, false, Box::new(Handling { handler }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::super::DataTracker;
+    use super::Propagation;
+
+    #[test]
+    fn first_handler_wins() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(1);
+        for &(key, priority, limit) in [("small", 0, 10), ("any", 1, i32::MAX)].iter() {
+            let seen2 = seen.clone();
+            tracked_data.add_handling_listener(key, priority, move |_: &i32, new_value: &i32| {
+                if *new_value < limit {
+                    seen2.lock().unwrap().push(key);
+                    Propagation::Stop
+                } else {
+                    Propagation::Continue
+                }
+            });
+        }
+        let seen2 = seen.clone();
+        tracked_data.add_listener_with_priority("last",
+                                                2,
+                                                move |_: &i32, _: &i32| {
+                                                    seen2.lock().unwrap().push("last");
+                                                });
+
+        tracked_data.set(5);
+        tracked_data.set(50);
+        assert!(*seen.lock().unwrap() == vec!["small", "any"]);
+    }
+}
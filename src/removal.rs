@@ -0,0 +1,141 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Removal of listeners requested while they are being called.
+
+use std::sync::{Arc, Mutex};
+
+use super::{Borrow, DataTracker, ListenerKey, Listeners};
+
+/// The keys whose callbacks are to be removed.
+pub type RemovalQueue<K> = Arc<Mutex<Vec<K>>>;
+
+/// Requests the removal of callbacks from a `DataTracker`, even from within
+/// one of its callbacks.
+///
+/// A callback cannot call `remove_listener()` on the tracker notifying it,
+/// so it can capture a `ListenerRemover` instead. Cloning a
+/// `ListenerRemover` gives another handle to the same tracker.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::listener_remover()`](./struct.DataTracker.html#method.listener_remover).
+#[derive(Clone)]
+pub struct ListenerRemover<K> {
+    queue: RemovalQueue<K>,
+}
+
+impl<K> ListenerRemover<K> {
+    /// Request the removal of the callback with the `key`.
+    ///
+    /// A removal requested while the listeners are being called is applied
+    /// after the current dispatch, once all listeners have been called for
+    /// the change being notified. Callbacks called later in the same dispatch
+    /// still run, even if their removal was requested. A removal requested
+    /// outside of a notification takes effect right away: the callback is
+    /// no longer reported by the tracker, and a callback added with the same
+    /// key afterwards is kept. Keys without a callback are ignored.
+    pub fn remove(&self, key: K) {
+        self.queue.lock().unwrap().push(key);
+    }
+}
+
+impl<C: ?Sized, K> Listeners<C, K>
+    where K: ListenerKey
+{
+    /// Remove the callbacks requested through a `ListenerRemover`.
+    pub fn apply_removals(&mut self) {
+        if let Some(ref queue) = self.removals {
+            for key in queue.lock().unwrap().drain(..) {
+                self.fn_map.remove(&key);
+            }
+        }
+    }
+
+    /// Return `true` if the removal of the callback with the `key` was
+    /// requested but not yet applied.
+    pub fn is_removal_pending<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + ListenerKey
+    {
+        match self.removals {
+            Some(ref queue) => queue.lock().unwrap().iter().any(|k| k.borrow() == key),
+            None => false,
+        }
+    }
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Return a `ListenerRemover` which callbacks can use to remove
+    /// themselves or other callbacks while being notified.
+    ///
+    /// This suits one-shot and conditional unsubscription, for example a
+    /// callback which removes itself once a threshold is reached.
+    ///
+    /// This is not available with the `no_std` feature.
+    pub fn listener_remover(&mut self) -> ListenerRemover<K> {
+        let queue = self.inner.listeners.removals.get_or_insert_with(Default::default);
+        ListenerRemover { queue: queue.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::super::DataTracker;
+
+    #[test]
+    fn remove_during_notification() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(0);
+        let remover = tracked_data.listener_remover();
+        let seen2 = seen.clone();
+        tracked_data.add_listener("limit", move |_: &i32, new_value: &i32| {
+            seen2.lock().unwrap().push(("limit", *new_value));
+            if *new_value >= 2 {
+                remover.remove("limit");
+                remover.remove("other");
+            }
+        });
+        let seen3 = seen.clone();
+        tracked_data.add_listener("other", move |_: &i32, new_value: &i32| {
+            seen3.lock().unwrap().push(("other", *new_value));
+        });
+
+        tracked_data.set(1);
+        // The removals are applied after this dispatch, so "other" still runs.
+        tracked_data.set(2);
+        assert!(!tracked_data.contains_listener("limit"));
+        assert!(!tracked_data.contains_listener("other"));
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() ==
+                vec![("limit", 1), ("other", 1), ("limit", 2), ("other", 2)]);
+    }
+
+    #[test]
+    fn remove_then_add_again() {
+        let calls = Arc::new(Mutex::new(0));
+        let mut tracked_data = DataTracker::new(0);
+        let remover = tracked_data.listener_remover();
+        tracked_data.add_listener("key", |_: &i32, _: &i32| panic!("removed callback called"));
+        remover.remove("key");
+        assert!(!tracked_data.contains_listener("key"));
+        assert!(tracked_data.listener_count() == 0);
+        assert!(!tracked_data.has_listeners());
+
+        let calls2 = calls.clone();
+        tracked_data.add_listener("key", move |_: &i32, _: &i32| {
+            *calls2.lock().unwrap() += 1;
+        });
+        tracked_data.set(1);
+        assert!(tracked_data.contains_listener("key"));
+        assert!(*calls.lock().unwrap() == 1);
+    }
+}
@@ -0,0 +1,440 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A tracker which can be shared between threads.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, TryLockError, Weak};
+use std::time::Duration;
+
+use super::cell::State;
+use super::{CasError, ListenerKey, OnChanged};
+
+struct Shared<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    state: Mutex<State<T, K>>,
+    /// Notified after every modification, see `wait_for_change()`.
+    changed: Condvar,
+}
+
+/// Tracks changes to data shared between threads and notifies listeners.
+///
+/// This works like [`DataTracker`](./struct.DataTracker.html), but the data
+/// lives behind an `Arc<Mutex<..>>`. Cloning a `SharedTracker` gives another
+/// handle to the same data and listeners. With the `no_send` feature, the
+/// listeners need not be `Send` and so neither is `SharedTracker`.
+///
+/// Listeners are called after the lock has been released, so they may
+/// themselves read or modify the tracker. Changes made while listeners are
+/// being called, whether from a listener or from another thread, are queued
+/// and passed to the listeners in order by the thread already calling them.
+/// Listeners added or removed while listeners are being called take effect
+/// once all queued changes have been delivered.
+pub struct SharedTracker<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    shared: Arc<Shared<T, K>>,
+}
+
+impl<T, K> SharedTracker<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    /// Create a new `SharedTracker` which takes ownership
+    /// of the data of type `T`.
+    pub fn new(value: T) -> SharedTracker<T, K> {
+        SharedTracker {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State::new(value)),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&self, key: K, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where C: OnChanged<T> + 'static
+    {
+        self.lock().inner.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned. While listeners are
+    /// being called, the removal is deferred and `None` is returned unless
+    /// the callback was added during that time.
+    pub fn remove_listener(&self, key: &K) -> Option<Box<dyn OnChanged<T>>>
+        where K: Clone
+    {
+        self.lock().remove_listener(key)
+    }
+
+    /// Add a callback which is removed when the returned `Subscription` is
+    /// dropped.
+    ///
+    /// This is like `add_listener()` followed by `remove_listener(&key)` when
+    /// the guard goes out of scope. The guard does not keep the tracker
+    /// alive. Note that if the callback of `key` is replaced in the meantime,
+    /// dropping the guard removes the replacement. Dropping the guard inside
+    /// the closure passed to `with_mut()` deadlocks, since the lock is held.
+    pub fn subscribe<C>(&self, key: K, callback: C) -> Subscription<T, K>
+        where K: Clone,
+              C: OnChanged<T> + 'static
+    {
+        self.lock().inner.listeners.insert(key.clone(), 0, false, Box::new(callback));
+        Subscription {
+            shared: Arc::downgrade(&self.shared),
+            key,
+        }
+    }
+
+    /// Return a copy of the current data.
+    pub fn get(&self) -> T {
+        self.lock().inner.value.clone()
+    }
+
+    /// Replace the data with `value`, notifying the listeners if it changed.
+    pub fn set(&self, value: T) {
+        self.with_mut(|v| *v = value);
+    }
+
+    /// Modify the data by calling `f` with the lock held and return its result.
+    ///
+    /// If the data changed, the listeners are called after the lock is
+    /// released and before `with_mut()` returns, unless another thread is
+    /// already calling them (in which case that thread delivers the change).
+    pub fn with_mut<F, R>(&self, f: F) -> R
+        where F: FnOnce(&mut T) -> R
+    {
+        let result = State::modify(self.lock(), || self.lock(), f);
+        self.shared.changed.notify_all();
+        result
+    }
+
+    /// Like `with_mut()`, but return `Err(WouldBlock)` instead of waiting if
+    /// the lock is held.
+    ///
+    /// The lock is held while another thread runs `get()`, `set()` or the
+    /// closure passed to `with_mut()`, or adds or removes a listener. It is
+    /// not held while the listeners are called, so this only fails for a
+    /// short time. In that case, `f` is not called.
+    pub fn try_with_mut<F, R>(&self, f: F) -> Result<R, WouldBlock>
+        where F: FnOnce(&mut T) -> R
+    {
+        let state = match self.shared.state.try_lock() {
+            Ok(state) => state,
+            Err(TryLockError::WouldBlock) => return Err(WouldBlock),
+            Err(TryLockError::Poisoned(e)) => panic!("{}", e),
+        };
+        let result = State::modify(state, || self.lock(), f);
+        self.shared.changed.notify_all();
+        Ok(result)
+    }
+
+    /// Block the calling thread until the data changes.
+    ///
+    /// Returns `true` once a change has been detected by another thread, or
+    /// `false` if `timeout` elapsed first. With a `timeout` of `None`, this
+    /// waits indefinitely. Spurious wakeups are handled by comparing the
+    /// `version()` before and after waiting. Like listeners, this only sees
+    /// changes made through this tracker and its clones.
+    pub fn wait_for_change(&self, timeout: Option<Duration>) -> bool {
+        let mut state = self.lock();
+        let version = state.inner.version;
+        state.waiters += 1;
+        let unchanged = |state: &mut State<T, K>| state.inner.version == version;
+        let mut state = match timeout {
+            None => self.shared.changed.wait_while(state, unchanged).unwrap(),
+            Some(timeout) => self.shared.changed.wait_timeout_while(state, timeout, unchanged).unwrap().0,
+        };
+        state.waiters -= 1;
+        state.inner.version != version
+    }
+
+    /// Block the calling thread until `pred(&value)` returns `true`.
+    ///
+    /// The predicate is checked right away, so this returns immediately if
+    /// it already holds, and then again after every modification. It is
+    /// called with the lock held.
+    pub fn wait_until<F>(&self, pred: F)
+        where F: Fn(&T) -> bool
+    {
+        let state = self.lock();
+        drop(self.shared.changed.wait_while(state, |state| !pred(&state.inner.value)).unwrap());
+    }
+
+    /// Like `wait_until()`, but give up once `timeout` elapsed.
+    ///
+    /// Returns `true` if the predicate holds, or `false` on timeout.
+    pub fn wait_until_timeout<F>(&self, pred: F, timeout: Duration) -> bool
+        where F: Fn(&T) -> bool
+    {
+        let state = self.lock();
+        let (state, _) = self.shared
+            .changed
+            .wait_timeout_while(state, timeout, |state| !pred(&state.inner.value))
+            .unwrap();
+        pred(&state.inner.value)
+    }
+
+    /// Return a counter which increments whenever the data may have changed.
+    ///
+    /// As with
+    /// [`DataTracker::version()`](./struct.DataTracker.html#method.version),
+    /// the count is conservative: a modification made while no listener is
+    /// registered and no thread is blocked in `wait_for_change()` is counted
+    /// even if it left the data unchanged.
+    pub fn version(&self) -> u64 {
+        self.lock().inner.version
+    }
+
+    /// Replace the data with `value` if the version equals
+    /// `expected_version`, or return `Err(CasError)` with the actual version.
+    ///
+    /// The version is checked and the data replaced with the lock held, so
+    /// of several threads passing the same `expected_version`, at most one
+    /// succeeds. Since `version()` is conservative, the call may fail after a
+    /// modification which left the data unchanged, but never succeeds after
+    /// a change made through this tracker or its clones.
+    pub fn compare_and_set(&self, expected_version: u64, value: T) -> Result<(), CasError> {
+        let state = self.lock();
+        if state.inner.version != expected_version {
+            return Err(CasError { actual: state.inner.version });
+        }
+        State::modify(state, || self.lock(), |v| *v = value);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T, K>> {
+        self.shared.state.lock().unwrap()
+    }
+}
+
+impl<T, K> Clone for SharedTracker<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey
+{
+    fn clone(&self) -> SharedTracker<T, K> {
+        SharedTracker { shared: self.shared.clone() }
+    }
+}
+
+/// The error returned by
+/// [`SharedTracker::try_with_mut()`](./struct.SharedTracker.html#method.try_with_mut)
+/// when the lock is held by someone else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WouldBlock;
+
+impl std::fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the tracker is locked")
+    }
+}
+
+impl std::error::Error for WouldBlock {}
+
+/// Removes a listener from a `SharedTracker` when dropped.
+///
+/// Create an instance of this by calling
+/// [`SharedTracker::subscribe()`](./struct.SharedTracker.html#method.subscribe).
+#[must_use = "dropping a Subscription immediately removes its listener"]
+pub struct Subscription<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey + Clone
+{
+    shared: Weak<Shared<T, K>>,
+    key: K,
+}
+
+impl<T, K> Subscription<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey + Clone
+{
+    /// Return the key under which the listener is registered.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<T, K> Drop for Subscription<T, K>
+    where T: Clone + PartialEq,
+          K: ListenerKey + Clone
+{
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.state.lock().unwrap().remove_listener(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::{SharedTracker, WouldBlock};
+
+    #[test]
+    fn subscription() {
+        let change_count = Arc::new(Mutex::new(0));
+        let tracker = SharedTracker::new(0);
+
+        let cc2 = change_count.clone();
+        let subscription = tracker.subscribe(0,
+                                             move |_: &i32, _: &i32| {
+                                                 *cc2.lock().unwrap() += 1;
+                                             });
+        assert!(*subscription.key() == 0);
+        tracker.set(1);
+        drop(subscription);
+        tracker.set(2);
+        assert!(*change_count.lock().unwrap() == 1);
+
+        // The guard may outlive the tracker.
+        let tracker = SharedTracker::new(0);
+        let subscription = tracker.subscribe(0, |_: &i32, _: &i32| {});
+        drop(tracker);
+        drop(subscription);
+    }
+
+    #[test]
+    fn compare_and_set() {
+        let tracker: SharedTracker<i32, u8> = SharedTracker::new(0);
+        tracker.add_listener(0, |_: &i32, _: &i32| {});
+        assert!(tracker.compare_and_set(0, 1).is_ok());
+        assert!(tracker.compare_and_set(0, 2).unwrap_err().actual_version() == 1);
+        assert!(tracker.get() == 1 && tracker.version() == 1);
+
+        let tracker: SharedTracker<i32, u8> = SharedTracker::new(0);
+        tracker.with_mut(|value| *value = 1);
+        assert!(tracker.compare_and_set(0, 2).is_err());
+        assert!(tracker.get() == 1);
+    }
+
+    #[test]
+    fn version_without_listeners() {
+        let tracker: SharedTracker<i32, u8> = SharedTracker::new(0);
+        tracker.with_mut(|value| *value = 1);
+        tracker.with_mut(|_| {});
+        assert!(tracker.version() == 2);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_send"))]
+    fn shared_between_threads() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let tracker = SharedTracker::new(0);
+
+        let seen2 = seen.clone();
+        tracker.add_listener(0,
+                             move |old_value: &i32, new_value: &i32| {
+                                 seen2.lock().unwrap().push((*old_value, *new_value));
+                             });
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let tracker = tracker.clone();
+                ::std::thread::spawn(move || for _ in 0..10 {
+                    tracker.with_mut(|v| *v += 1);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(tracker.get() == 40);
+        let seen = seen.lock().unwrap();
+        assert!(seen.len() == 40);
+        // Changes are delivered in order.
+        for (i, &(old_value, new_value)) in seen.iter().enumerate() {
+            assert!(old_value == i as i32 && new_value == i as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn try_with_mut() {
+        let tracker = SharedTracker::<i32, u8>::new(0);
+        assert!(tracker.try_with_mut(|v| *v = 1) == Ok(()));
+
+        let tracker2 = tracker.clone();
+        let result = tracker.with_mut(|_| tracker2.try_with_mut(|v| *v = 2));
+        assert!(result == Err(WouldBlock));
+        assert!(tracker.get() == 1);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_send"))]
+    fn wait_for_change() {
+        let tracker: SharedTracker<i32, u8> = SharedTracker::new(0);
+        assert!(!tracker.wait_for_change(Some(::std::time::Duration::from_millis(10))));
+
+        let tracker2 = tracker.clone();
+        let handle = ::std::thread::spawn(move || {
+            ::std::thread::sleep(::std::time::Duration::from_millis(10));
+            tracker2.set(0);
+            tracker2.set(1);
+        });
+        assert!(tracker.wait_for_change(None));
+        handle.join().unwrap();
+        assert!(tracker.get() == 1 && tracker.version() == 1);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_send"))]
+    fn wait_until() {
+        let tracker: SharedTracker<i32, u8> = SharedTracker::new(0);
+        // Already true.
+        tracker.wait_until(|v| *v == 0);
+        assert!(!tracker.wait_until_timeout(|v| *v == 1, ::std::time::Duration::from_millis(10)));
+
+        let tracker2 = tracker.clone();
+        let handle = ::std::thread::spawn(move || for _ in 0..5 {
+            tracker2.with_mut(|v| *v += 1);
+        });
+        tracker.wait_until(|v| *v >= 5);
+        handle.join().unwrap();
+        assert!(tracker.wait_until_timeout(|v| *v == 5, ::std::time::Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn reentrant_listener() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let tracker = SharedTracker::new(0);
+
+        // A listener which modifies the tracker and adds another listener.
+        let tracker2 = tracker.clone();
+        let seen2 = seen.clone();
+        tracker.add_listener(0,
+                             move |_: &i32, new_value: &i32| {
+            if *new_value < 3 {
+                tracker2.set(*new_value + 1);
+            }
+            let seen3 = seen2.clone();
+            tracker2.add_listener(1,
+                                  move |_: &i32, new_value: &i32| {
+                                      seen3.lock().unwrap().push(*new_value);
+                                  });
+        });
+
+        tracker.set(1);
+        assert!(tracker.get() == 3);
+        // The listener added during dispatch only sees later changes.
+        assert!(seen.lock().unwrap().is_empty());
+        tracker.set(10);
+        assert!(*seen.lock().unwrap() == vec![10]);
+
+        assert!(tracker.remove_listener(&1).is_some());
+        assert!(tracker.remove_listener(&1).is_none());
+    }
+}
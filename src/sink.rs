@@ -0,0 +1,155 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A single receiver of every change, with metadata.
+
+use std::time::SystemTime;
+
+use super::{DataTracker, Exclusive, Inner, ListenerKey};
+
+/// A detected change, as passed to a `ChangeSink`.
+pub struct ChangeEvent<'a, T: 'a> {
+    timestamp: SystemTime,
+    version: u64,
+    old_value: &'a T,
+    new_value: &'a T,
+}
+
+impl<'a, T> ChangeEvent<'a, T> {
+    fn new(version: u64, old_value: &'a T, new_value: &'a T) -> ChangeEvent<'a, T> {
+        ChangeEvent {
+            timestamp: SystemTime::now(),
+            version,
+            old_value,
+            new_value,
+        }
+    }
+
+    /// Return the time at which the change was detected.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Return the version of the tracker after the change, see
+    /// [`DataTracker::version()`](./struct.DataTracker.html#method.version).
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Return the value from before the change.
+    pub fn old_value(&self) -> &T {
+        self.old_value
+    }
+
+    /// Return the value after the change.
+    pub fn new_value(&self) -> &T {
+        self.new_value
+    }
+}
+
+/// Trait defining a receiver of all changes of a tracker.
+#[cfg(not(feature = "no_send"))]
+pub trait ChangeSink<T>: Send {
+    fn on_change(&self, event: &ChangeEvent<'_, T>);
+}
+
+#[cfg(feature = "no_send")]
+pub trait ChangeSink<T> {
+    fn on_change(&self, event: &ChangeEvent<'_, T>);
+}
+
+/// Lets a boxed sink be passed wherever a sink is expected.
+impl<T> ChangeSink<T> for Box<dyn ChangeSink<T>> {
+    fn on_change(&self, event: &ChangeEvent<'_, T>) {
+        (**self).on_change(event)
+    }
+}
+
+#[cfg(not(feature = "no_send"))]
+impl<F, T> ChangeSink<T> for F
+    where F: Fn(&ChangeEvent<'_, T>) + Send
+{
+    fn on_change(&self, event: &ChangeEvent<'_, T>) {
+        self(event)
+    }
+}
+
+#[cfg(feature = "no_send")]
+impl<F, T> ChangeSink<T> for F
+    where F: Fn(&ChangeEvent<'_, T>)
+{
+    fn on_change(&self, event: &ChangeEvent<'_, T>) {
+        self(event)
+    }
+}
+
+impl<T, K> Inner<T, K>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Pass a detected change from `old_value` to the sink, if any.
+    pub fn notify_sink(&mut self, old_value: &T) {
+        if let Some(ref mut sink) = self.sink {
+            sink.get_mut().on_change(&ChangeEvent::new(self.version, old_value, &self.value));
+        }
+    }
+}
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Install `sink` to receive every detected change.
+    ///
+    /// The sink is separate from the keyed listeners: it is called before
+    /// them for every change, whether or not any listeners are registered,
+    /// and receives a [`ChangeEvent`](./struct.ChangeEvent.html) with a
+    /// timestamp and the version. This suits centralized auditing. There is
+    /// at most one sink, so a previous sink is returned as `Some(sink)`.
+    /// Otherwise, `None` is returned. A panic of the sink is not caught.
+    ///
+    /// This is not available with the `no_std` feature.
+    pub fn set_change_sink<S>(&mut self, sink: S) -> Option<Box<dyn ChangeSink<T>>>
+        where S: ChangeSink<T> + 'static
+    {
+        self.inner.sink.replace(Exclusive(Box::new(sink))).map(Exclusive::into_inner)
+    }
+
+    /// Remove the sink installed with `set_change_sink()` and return it.
+    pub fn take_change_sink(&mut self) -> Option<Box<dyn ChangeSink<T>>> {
+        self.inner.sink.take().map(Exclusive::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::super::DataTracker;
+    use super::ChangeEvent;
+
+    #[test]
+    fn change_sink() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data: DataTracker<i32, u8> = DataTracker::new(1);
+        let seen2 = seen.clone();
+        tracked_data.set_change_sink(move |event: &ChangeEvent<'_, i32>| {
+            let change = (event.version(), *event.old_value(), *event.new_value());
+            seen2.lock().unwrap().push(change);
+        });
+
+        // Reported without any listeners.
+        *tracked_data.as_tracked_mut() = 2;
+        tracked_data.set(2);
+        tracked_data.set(3);
+        assert!(*seen.lock().unwrap() == vec![(1, 1, 2), (2, 2, 3)]);
+
+        assert!(tracked_data.take_change_sink().is_some());
+        tracked_data.set(4);
+        assert!(seen.lock().unwrap().len() == 2);
+    }
+}
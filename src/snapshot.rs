@@ -0,0 +1,229 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Change detection using a user defined snapshot rather than a copy.
+
+use alloc::boxed::Box;
+
+use super::{ListenerKey, Listeners, MaybeSend};
+
+/// Trait defining a change notification callback function which receives
+/// the snapshot taken before the change and the new value.
+#[cfg(not(feature = "no_send"))]
+pub trait SnapshotOnChanged<S, T>: Send {
+    fn on_changed(&self, snapshot: &S, new_value: &T);
+}
+
+#[cfg(feature = "no_send")]
+pub trait SnapshotOnChanged<S, T> {
+    fn on_changed(&self, snapshot: &S, new_value: &T);
+}
+
+#[cfg(not(feature = "no_send"))]
+impl<F, S, T> SnapshotOnChanged<S, T> for F
+    where F: Fn(&S, &T) + Send
+{
+    fn on_changed(&self, snapshot: &S, new_value: &T) {
+        self(snapshot, new_value)
+    }
+}
+
+#[cfg(feature = "no_send")]
+impl<F, S, T> SnapshotOnChanged<S, T> for F
+    where F: Fn(&S, &T)
+{
+    fn on_changed(&self, snapshot: &S, new_value: &T) {
+        self(snapshot, new_value)
+    }
+}
+
+impl<S, T> SnapshotOnChanged<S, T> for Box<dyn SnapshotOnChanged<S, T>> {
+    fn on_changed(&self, snapshot: &S, new_value: &T) {
+        (**self).on_changed(snapshot, new_value)
+    }
+}
+
+#[cfg(not(feature = "no_send"))]
+type SnapshotFn<T, S> = Box<dyn Fn(&T) -> S + Send>;
+
+#[cfg(feature = "no_send")]
+type SnapshotFn<T, S> = Box<dyn Fn(&T) -> S>;
+
+#[cfg(not(feature = "no_send"))]
+type ChangedFn<S, T> = Box<dyn Fn(&S, &T) -> bool + Send>;
+
+#[cfg(feature = "no_send")]
+type ChangedFn<S, T> = Box<dyn Fn(&S, &T) -> bool>;
+
+/// Tracks changes to data by comparing against a snapshot and notifies
+/// listeners.
+///
+/// This works like [`DataTracker`](./struct.DataTracker.html), but `T` needs
+/// neither `Clone` nor `PartialEq`. Instead, when a
+/// [`SnapshotModifier`](./struct.SnapshotModifier.html) is created,
+/// `snapshot(&value)` stores a value of type `S`, typically a cheap
+/// fingerprint such as a length, a version number or a hash. When the
+/// modifier is dropped, `changed(&snapshot, &value)` decides whether a change
+/// occurred, in which case the listeners are called with the snapshot and the
+/// new value.
+pub struct SnapshotTracker<T, S, K>
+    where K: ListenerKey
+{
+    value: T,
+    snapshot: SnapshotFn<T, S>,
+    changed: ChangedFn<S, T>,
+    listeners: Listeners<dyn SnapshotOnChanged<S, T>, K>,
+}
+
+impl<T, S, K> SnapshotTracker<T, S, K>
+    where K: ListenerKey
+{
+    /// Create a new `SnapshotTracker` which takes ownership of the data of
+    /// type `T`, describes it using `snapshot(value)` and detects changes
+    /// using `changed(snapshot, new_value)`.
+    pub fn new<F, G>(value: T, snapshot: F, changed: G) -> SnapshotTracker<T, S, K>
+        where F: Fn(&T) -> S + MaybeSend + 'static,
+              G: Fn(&S, &T) -> bool + MaybeSend + 'static
+    {
+        SnapshotTracker {
+            value,
+            snapshot: Box::new(snapshot),
+            changed: Box::new(changed),
+            listeners: Listeners::new(),
+        }
+    }
+
+    /// Add a callback that will be called just after a data change is detected.
+    ///
+    /// If a previous callback exists with the `key`, the original callback is
+    /// returned as `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<C>(&mut self,
+                           key: K,
+                           callback: C)
+                           -> Option<Box<dyn SnapshotOnChanged<S, T>>>
+        where C: SnapshotOnChanged<S, T> + 'static
+    {
+        self.listeners.insert(key, 0, false, Box::new(callback))
+    }
+
+    /// Remove callback.
+    ///
+    /// If a callback exists with the `key`, it is removed and returned as
+    /// `Some(callback)`. Otherwise, `None` is returned.
+    pub fn remove_listener(&mut self, key: &K) -> Option<Box<dyn SnapshotOnChanged<S, T>>> {
+        self.listeners.remove(key)
+    }
+
+    /// Return a `SnapshotModifier` which can be used to modify the owned data.
+    ///
+    /// If no listeners are registered, no snapshot is taken.
+    pub fn as_tracked_mut(&mut self) -> SnapshotModifier<'_, T, S, K> {
+        let snapshot = if self.listeners.fn_map.is_empty() {
+            None
+        } else {
+            Some((self.snapshot)(&self.value))
+        };
+        SnapshotModifier {
+            snapshot,
+            tracker: self,
+        }
+    }
+
+    /// Consume the `SnapshotTracker` and return the owned data.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, S, K> AsRef<T> for SnapshotTracker<T, S, K>
+    where K: ListenerKey
+{
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Allow viewing and modifying data owned by `SnapshotTracker`.
+///
+/// Create an instance of this by calling
+/// [`SnapshotTracker::as_tracked_mut()`](./struct.SnapshotTracker.html#method.as_tracked_mut).
+pub struct SnapshotModifier<'a, T: 'a, S: 'a, K>
+    where K: 'a + ListenerKey
+{
+    snapshot: Option<S>,
+    tracker: &'a mut SnapshotTracker<T, S, K>,
+}
+
+impl<'a, T, S, K> std::ops::Deref for SnapshotModifier<'a, T, S, K>
+    where K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.tracker.value
+    }
+}
+
+impl<'a, T, S, K> std::ops::DerefMut for SnapshotModifier<'a, T, S, K>
+    where K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.tracker.value
+    }
+}
+
+impl<'a, T, S, K> Drop for SnapshotModifier<'a, T, S, K>
+    where K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            let tracker = &mut *self.tracker;
+            if (tracker.changed)(&snapshot, &tracker.value) {
+                let new_value = &tracker.value;
+                tracker.listeners.dispatch(|callback| {
+                    callback.on_changed(&snapshot, new_value);
+                    Ok(())
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::SnapshotTracker;
+
+    #[test]
+    fn track_by_snapshot() {
+        // Neither Clone nor PartialEq is required.
+        struct Log {
+            lines: Vec<String>,
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = SnapshotTracker::new(Log { lines: Vec::new() },
+                                                    |log: &Log| log.lines.len(),
+                                                    |len: &usize, log: &Log| *len != log.lines.len());
+
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |len: &usize, log: &Log| {
+                                      seen2.lock().unwrap().push((*len, log.lines.len()));
+                                  });
+
+        tracked_data.as_tracked_mut().lines.push("a".to_string());
+        {
+            // Not detected, since the snapshot only covers the length.
+            let mut x = tracked_data.as_tracked_mut();
+            x.lines[0] = "b".to_string();
+        }
+        assert!(*seen.lock().unwrap() == vec![(0, 1)]);
+        assert!(tracked_data.into_inner().lines == vec!["b".to_string()]);
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Listeners which deliver changes later, from a timer thread.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Job, OnChanged};
+
+/// A job to be run by the timer thread once `deadline` has passed.
+struct Scheduled {
+    deadline: Instant,
+    job: Job,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Scheduled) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Scheduled) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    // Reversed, so that the `BinaryHeap` yields the earliest deadline first.
+    fn cmp(&self, other: &Scheduled) -> Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+/// The jobs of the timer thread, which is shared by all timed listeners.
+struct Timer {
+    jobs: Mutex<BinaryHeap<Scheduled>>,
+    cvar: Condvar,
+}
+
+/// Return the timer, starting its thread on first use.
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    TIMER.get_or_init(|| {
+        // The thread waits for the initialization to finish.
+        thread::spawn(|| run_timer(timer()));
+        Timer {
+            jobs: Mutex::new(BinaryHeap::new()),
+            cvar: Condvar::new(),
+        }
+    })
+}
+
+fn run_timer(timer: &Timer) {
+    let mut jobs = timer.jobs.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        match jobs.peek().map(|scheduled| scheduled.deadline) {
+            None => jobs = timer.cvar.wait(jobs).unwrap(),
+            Some(deadline) if now < deadline => {
+                jobs = timer.cvar.wait_timeout(jobs, deadline - now).unwrap().0;
+            }
+            Some(_) => {
+                let job = jobs.pop().unwrap().job;
+                drop(jobs);
+                // A panicking callback must not stop the other timed listeners.
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                jobs = timer.jobs.lock().unwrap();
+            }
+        }
+    }
+}
+
+/// Run `job` on the timer thread once `deadline` has passed.
+fn schedule(deadline: Instant, job: Job) {
+    let timer = timer();
+    timer.jobs.lock().unwrap().push(Scheduled { deadline, job });
+    timer.cvar.notify_one();
+}
+
+struct DebounceState<T> {
+    pending: Option<(T, T)>,
+    deadline: Instant,
+    /// `true` while a job of the timer is due to check the deadline.
+    scheduled: bool,
+    closed: bool,
+}
+
+struct DebounceShared<T> {
+    state: Mutex<DebounceState<T>>,
+    callback: Mutex<Box<dyn OnChanged<T>>>,
+}
+
+/// Collapses rapid changes and delivers them from the timer thread.
+///
+/// Dropping this cancels any pending notification.
+pub struct Debounced<T> {
+    shared: Arc<DebounceShared<T>>,
+    duration: Duration,
+}
+
+impl<T> Debounced<T> {
+    pub fn new<C>(duration: Duration, callback: C) -> Debounced<T>
+        where C: OnChanged<T> + 'static
+    {
+        let state = DebounceState {
+            pending: None,
+            deadline: Instant::now(),
+            scheduled: false,
+            closed: false,
+        };
+        let shared = DebounceShared {
+            state: Mutex::new(state),
+            callback: Mutex::new(Box::new(callback)),
+        };
+        Debounced {
+            shared: Arc::new(shared),
+            duration,
+        }
+    }
+}
+
+fn debounce_fire<T>(shared: Arc<DebounceShared<T>>)
+    where T: Send + 'static
+{
+    let mut state = shared.state.lock().unwrap();
+    if state.closed {
+        return;
+    }
+    // A change since the job was scheduled pushed the deadline back.
+    if Instant::now() < state.deadline {
+        let deadline = state.deadline;
+        drop(state);
+        schedule(deadline, Box::new(move || debounce_fire(shared)));
+        return;
+    }
+    state.scheduled = false;
+    let (old_value, new_value) = match state.pending.take() {
+        Some(change) => change,
+        None => return,
+    };
+    drop(state);
+    shared.callback.lock().unwrap().on_changed(&old_value, &new_value);
+}
+
+impl<T> OnChanged<T> for Debounced<T>
+    where T: Clone + Send + 'static
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.pending {
+            Some((_, ref mut pending_new)) => *pending_new = new_value.clone(),
+            None => state.pending = Some((old_value.clone(), new_value.clone())),
+        }
+        state.deadline = Instant::now() + self.duration;
+        if !state.scheduled {
+            state.scheduled = true;
+            let shared = self.shared.clone();
+            schedule(state.deadline, Box::new(move || debounce_fire(shared)));
+        }
+    }
+}
+
+impl<T> Drop for Debounced<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.pending = None;
+    }
+}
+
+struct WindowShared<T, F> {
+    batch: Mutex<Vec<(T, T)>>,
+    callback: Mutex<F>,
+}
+
+/// Collects the changes within a time window and delivers them from the
+/// timer thread as one batch.
+///
+/// Dropping this delivers any collected changes right away.
+pub struct Windowed<T, F>
+    where T: Send + 'static,
+          F: Fn(&[(T, T)]) + Send + 'static
+{
+    shared: Arc<WindowShared<T, F>>,
+    window: Duration,
+}
+
+impl<T, F> Windowed<T, F>
+    where T: Send + 'static,
+          F: Fn(&[(T, T)]) + Send + 'static
+{
+    pub fn new(window: Duration, callback: F) -> Windowed<T, F> {
+        let shared = WindowShared {
+            batch: Mutex::new(Vec::new()),
+            callback: Mutex::new(callback),
+        };
+        Windowed {
+            shared: Arc::new(shared),
+            window,
+        }
+    }
+
+    /// Deliver the collected changes from the timer thread once `deadline`
+    /// has passed.
+    fn schedule_delivery(&self, deadline: Instant) {
+        let shared = self.shared.clone();
+        schedule(deadline, Box::new(move || window_fire(&shared)));
+    }
+}
+
+fn window_fire<T, F>(shared: &WindowShared<T, F>)
+    where F: Fn(&[(T, T)])
+{
+    let batch = std::mem::take(&mut *shared.batch.lock().unwrap());
+    // The batch may have been delivered early, when the tracker was dropped.
+    if !batch.is_empty() {
+        (shared.callback.lock().unwrap())(&batch);
+    }
+}
+
+impl<T, F> OnChanged<T> for Windowed<T, F>
+    where T: Clone + Send + 'static,
+          F: Fn(&[(T, T)]) + Send + 'static
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let mut batch = self.shared.batch.lock().unwrap();
+        if batch.is_empty() {
+            self.schedule_delivery(Instant::now() + self.window);
+        }
+        batch.push((old_value.clone(), new_value.clone()));
+    }
+}
+
+impl<T, F> Drop for Windowed<T, F>
+    where T: Send + 'static,
+          F: Fn(&[(T, T)]) + Send + 'static
+{
+    fn drop(&mut self) {
+        if !self.shared.batch.lock().unwrap().is_empty() {
+            self.schedule_delivery(Instant::now());
+        }
+    }
+}
+
+struct RateState<T> {
+    pending: Option<(T, T)>,
+    /// The earliest time of the next delivery.
+    next_delivery: Instant,
+    /// `true` while a job of the timer is due to deliver `pending`.
+    scheduled: bool,
+    closed: bool,
+}
+
+struct RateShared<T> {
+    state: Mutex<RateState<T>>,
+    callback: Mutex<Box<dyn OnChanged<T>>>,
+    min_interval: Duration,
+}
+
+/// Delivers changes from the timer thread at most once per interval.
+///
+/// Dropping this cancels any pending notification.
+pub struct RateLimited<T> {
+    shared: Arc<RateShared<T>>,
+}
+
+impl<T> RateLimited<T> {
+    pub fn new<C>(min_interval: Duration, callback: C) -> RateLimited<T>
+        where C: OnChanged<T> + 'static
+    {
+        let state = RateState {
+            pending: None,
+            next_delivery: Instant::now(),
+            scheduled: false,
+            closed: false,
+        };
+        let shared = RateShared {
+            state: Mutex::new(state),
+            callback: Mutex::new(Box::new(callback)),
+            min_interval,
+        };
+        RateLimited { shared: Arc::new(shared) }
+    }
+}
+
+fn rate_fire<T>(shared: &RateShared<T>) {
+    let mut state = shared.state.lock().unwrap();
+    state.scheduled = false;
+    if state.closed {
+        return;
+    }
+    let (old_value, new_value) = match state.pending.take() {
+        Some(change) => change,
+        None => return,
+    };
+    state.next_delivery = Instant::now() + shared.min_interval;
+    drop(state);
+    shared.callback.lock().unwrap().on_changed(&old_value, &new_value);
+}
+
+impl<T> OnChanged<T> for RateLimited<T>
+    where T: Clone + Send + 'static
+{
+    fn on_changed(&self, old_value: &T, new_value: &T) {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.pending {
+            Some((_, ref mut pending_new)) => *pending_new = new_value.clone(),
+            None => state.pending = Some((old_value.clone(), new_value.clone())),
+        }
+        if !state.scheduled {
+            state.scheduled = true;
+            let shared = self.shared.clone();
+            schedule(state.next_delivery, Box::new(move || rate_fire(&shared)));
+        }
+    }
+}
+
+impl<T> Drop for RateLimited<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.pending = None;
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Modifications which can be committed or rolled back.
+
+use super::{DataTracker, Inner, ListenerKey};
+
+impl<T, K, I> DataTracker<T, K, I>
+    where T: Clone,
+          K: ListenerKey
+{
+    /// Return a `Transaction` which can be used to modify the owned data and
+    /// then either commit or roll back the modifications.
+    ///
+    /// Unlike `as_tracked_mut()`, a copy of the data is always made, since it
+    /// is needed for a rollback even without listeners.
+    pub fn begin_transaction(&mut self) -> Transaction<'_, T, K> {
+        self.inner.check_readers();
+        Transaction {
+            snapshot: Some(self.inner.value.clone()),
+            inner_ref: &mut self.inner,
+        }
+    }
+}
+
+/// Allow modifying data owned by `DataTracker` with the option to undo all
+/// modifications.
+///
+/// Create an instance of this by calling
+/// [`DataTracker::begin_transaction()`](./struct.DataTracker.html#method.begin_transaction).
+/// Modifications made through it are only reported to the listeners by
+/// `commit()`. Calling `rollback()`, or dropping the `Transaction` without
+/// committing, restores the data to its value when the transaction began and
+/// does not notify.
+#[must_use = "dropping a Transaction rolls it back"]
+pub struct Transaction<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    snapshot: Option<T>,
+    inner_ref: &'a mut Inner<T, K>,
+}
+
+impl<'a, T, K> Transaction<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    /// Keep the modifications and notify the listeners if the data changed.
+    ///
+    /// Returns `true` if the data changed and the listeners were called.
+    pub fn commit(mut self) -> bool {
+        let snapshot = match self.snapshot.take() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        self.inner_ref.normalize();
+        if self.inner_ref.changed_from(&snapshot) {
+            self.inner_ref.notify_listeners(&snapshot);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Discard all modifications, restoring the data to its value when the
+    /// transaction began, without notifying the listeners.
+    pub fn rollback(self) {}
+}
+
+impl<'a, T, K> std::ops::Deref for Transaction<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner_ref.value
+    }
+}
+
+impl<'a, T, K> std::ops::DerefMut for Transaction<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner_ref.value
+    }
+}
+
+impl<'a, T, K> Drop for Transaction<'a, T, K>
+    where T: 'a + Clone,
+          K: 'a + ListenerKey
+{
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            self.inner_ref.value = snapshot;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::super::DataTracker;
+
+    #[test]
+    fn commit_and_rollback() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = DataTracker::new(vec![1]);
+        let seen2 = seen.clone();
+        tracked_data.add_listener(0,
+                                  move |old_value: &Vec<i32>, new_value: &Vec<i32>| {
+                                      seen2.lock().unwrap().push((old_value.clone(),
+                                                                  new_value.clone()));
+                                  });
+
+        let mut t = tracked_data.begin_transaction();
+        for i in 2..10 {
+            t.push(i);
+        }
+        t.clear();
+        t.push(5);
+        t.rollback();
+        assert!(*tracked_data.as_ref() == vec![1]);
+        {
+            // Dropping without committing also rolls back.
+            let mut t = tracked_data.begin_transaction();
+            t.push(2);
+        }
+        assert!(*tracked_data.as_ref() == vec![1]);
+        assert!(seen.lock().unwrap().is_empty());
+
+        let mut t = tracked_data.begin_transaction();
+        t.push(2);
+        assert!(t.commit());
+        assert!(*seen.lock().unwrap() == vec![(vec![1], vec![1, 2])]);
+    }
+}
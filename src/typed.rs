@@ -0,0 +1,123 @@
+// Copyright 2017 Andrew D. Straw.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Change tracking with callbacks registered per subscriber type.
+
+use alloc::boxed::Box;
+use std::any::{Any, TypeId};
+
+use super::{DataTracker, Modifier, OnChanged};
+
+/// Tracks changes to data and notifies listeners, keyed by the type of the
+/// subscriber.
+///
+/// This works like a [`DataTracker`](./struct.DataTracker.html) whose keys
+/// are the `TypeId` of a subscriber type `S` given as a type parameter, so
+/// that no key values need to be invented. Each subscriber type has at most
+/// one callback.
+pub struct TypeKeyedTracker<T>
+    where T: Clone
+{
+    tracker: DataTracker<T, TypeId>,
+}
+
+impl<T> TypeKeyedTracker<T>
+    where T: Clone + PartialEq
+{
+    /// Create a new `TypeKeyedTracker` which takes ownership
+    /// of the data of type `T`.
+    pub fn new(value: T) -> TypeKeyedTracker<T> {
+        TypeKeyedTracker { tracker: DataTracker::new(value) }
+    }
+}
+
+impl<T> TypeKeyedTracker<T>
+    where T: Clone
+{
+    /// Add the callback of subscriber type `S`, which will be called just
+    /// after a data change is detected.
+    ///
+    /// If `S` already has a callback, the original callback is returned as
+    /// `Some(original_callback)`. Otherwise, `None` is returned.
+    pub fn add_listener<S, C>(&mut self, callback: C) -> Option<Box<dyn OnChanged<T>>>
+        where S: ?Sized + Any,
+              C: OnChanged<T> + 'static
+    {
+        self.tracker.add_listener(TypeId::of::<S>(), callback)
+    }
+
+    /// Remove the callback of subscriber type `S`.
+    ///
+    /// If `S` has a callback, it is removed and returned as `Some(callback)`.
+    /// Otherwise, `None` is returned.
+    pub fn remove_listener<S>(&mut self) -> Option<Box<dyn OnChanged<T>>>
+        where S: ?Sized + Any
+    {
+        self.tracker.remove_listener(&TypeId::of::<S>())
+    }
+
+    /// Return `true` if subscriber type `S` has a callback.
+    pub fn contains_listener<S>(&self) -> bool
+        where S: ?Sized + Any
+    {
+        self.tracker.contains_listener(&TypeId::of::<S>())
+    }
+
+    /// Return a `Modifier` which can be used to modify the owned data.
+    pub fn as_tracked_mut(&mut self) -> Modifier<'_, T, TypeId> {
+        self.tracker.as_tracked_mut()
+    }
+
+    /// Replace the owned data with `value`, notifying the listeners if it
+    /// changed.
+    pub fn set(&mut self, value: T) {
+        self.tracker.set(value)
+    }
+}
+
+impl<T> AsRef<T> for TypeKeyedTracker<T>
+    where T: Clone
+{
+    fn as_ref(&self) -> &T {
+        self.tracker.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::TypeKeyedTracker;
+
+    struct Logger;
+    struct Renderer;
+
+    #[test]
+    fn one_listener_per_type() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracked_data = TypeKeyedTracker::new(1);
+        for name in ["logger", "logger again"].iter() {
+            let seen2 = seen.clone();
+            tracked_data.add_listener::<Logger, _>(move |_: &i32, new_value: &i32| {
+                seen2.lock().unwrap().push((*name, *new_value));
+            });
+        }
+        let seen3 = seen.clone();
+        tracked_data.add_listener::<Renderer, _>(move |_: &i32, new_value: &i32| {
+            seen3.lock().unwrap().push(("renderer", *new_value));
+        });
+
+        *tracked_data.as_tracked_mut() = 2;
+        assert!(*seen.lock().unwrap() == vec![("logger again", 2), ("renderer", 2)]);
+
+        assert!(tracked_data.remove_listener::<Logger>().is_some());
+        assert!(!tracked_data.contains_listener::<Logger>());
+        assert!(tracked_data.contains_listener::<Renderer>());
+        tracked_data.set(3);
+        assert!(seen.lock().unwrap().len() == 3 && *tracked_data.as_ref() == 3);
+    }
+}
@@ -0,0 +1,79 @@
+// Field tracking is not available without std.
+#![cfg(not(feature = "no_std"))]
+
+extern crate data_tracker;
+#[macro_use]
+extern crate data_tracker_derive;
+
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use data_tracker::FieldTracker;
+
+#[derive(Clone, PartialEq, TrackFields)]
+pub struct Settings {
+    pub volume: u8,
+    /// Doc comments are attributes too.
+    pub(crate) muted: bool,
+    name_tag: Option<Vec<(u8, u16)>>,
+    marker: PhantomData<fn(u8) -> u8>,
+    #[track(skip)]
+    last_accessed: u64,
+}
+
+#[test]
+fn changed_fields() {
+    let old = Settings {
+        volume: 1,
+        muted: false,
+        name_tag: None,
+        marker: PhantomData,
+        last_accessed: 0,
+    };
+    let mut new = old.clone();
+    assert!(data_tracker::TrackFields::changed_fields(&old, &new).is_empty());
+
+    new.muted = true;
+    new.name_tag = Some(vec![(1, 2)]);
+    new.last_accessed = 10;
+    assert!(data_tracker::TrackFields::changed_fields(&old, &new) == vec![SettingsField::Muted, SettingsField::NameTag]);
+    let _ = (SettingsField::Volume, SettingsField::Marker);
+}
+
+#[test]
+fn field_tracker() {
+    let settings = Settings {
+        volume: 1,
+        muted: false,
+        name_tag: None,
+        marker: PhantomData,
+        last_accessed: 0,
+    };
+    let mut tracked_data = FieldTracker::new(settings);
+
+    let volume_changes = Arc::new(Mutex::new(0));
+    let vc2 = volume_changes.clone();
+    tracked_data.add_listener(SettingsField::Volume,
+                              0,
+                              move |old_value: &Settings, new_value: &Settings| {
+                                  assert!(old_value.volume == 1);
+                                  assert!(new_value.volume == 2);
+                                  *vc2.lock().unwrap() += 1;
+                              });
+
+    {
+        let mut x = tracked_data.as_tracked_mut();
+        x.muted = true;
+        x.last_accessed = 5;
+    }
+    assert!(*volume_changes.lock().unwrap() == 0);
+
+    {
+        let mut x = tracked_data.as_tracked_mut();
+        x.volume = 2;
+    }
+    assert!(*volume_changes.lock().unwrap() == 1);
+
+    assert!(tracked_data.remove_listener(SettingsField::Volume, &0).is_some());
+    assert!(tracked_data.into_inner().muted);
+}